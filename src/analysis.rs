@@ -0,0 +1,225 @@
+/* analysis.rs
+ *
+ * Copyright 2026 Will Warner
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use crate::{games, runtime, window::SolitaireWindow};
+use adw::prelude::*;
+use gettextrs::gettext;
+use gtk::glib;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// What the solver found out about a position
+#[derive(Clone)]
+enum Outcome {
+    Winnable(Vec<runtime::Move>),
+    // The solver gave up before finding out
+    Unknown,
+    Lost,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Verdict {
+    // The move the solver would have made
+    Good,
+    Neutral,
+    // The move that made a winnable game lost
+    Losing,
+}
+
+fn verdict(before: &Outcome, after: &Outcome, move_: Option<&runtime::Move>) -> Verdict {
+    match (before, after) {
+        (Outcome::Winnable(_), Outcome::Lost) => Verdict::Losing,
+        (Outcome::Winnable(solution), _) if move_.is_some() && solution.first() == move_ => {
+            Verdict::Good
+        }
+        _ => Verdict::Neutral,
+    }
+}
+
+/// Re-solves every position of the game in the background and shows how
+/// each move changed the chances of winning
+pub fn analyze(window: &SolitaireWindow) {
+    let game_board = window.get_gameboard();
+    let (positions, history) = runtime::get_positions(&game_board);
+    if history.is_empty() {
+        window.show_toast(&gettext("No moves to analyze yet"));
+        return;
+    }
+    let (stack_names, _game_state) = runtime::get_solver_state(&game_board);
+    let mut solvers = Vec::new();
+    for _ in &positions {
+        let Some(solver_fns) = games::solver_fns(&game_board) else {
+            return;
+        };
+        solvers.push(solver_fns);
+    }
+
+    let list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(["boxed-list"])
+        .build();
+    let mut rows = Vec::new();
+    for (i, transaction) in history.iter().enumerate() {
+        let description = transaction
+            .moves()
+            .filter_map(|move_| runtime::describe_move(&game_board, move_))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let row = adw::ActionRow::builder()
+            .title(gettext("Move {}").replace("{}", &(i + 1).to_string()))
+            .subtitle(description)
+            .build();
+        let status = gtk::Label::new(Some(&gettext("Analyzing…")));
+        status.add_css_class("dim-label");
+        row.add_suffix(&status);
+        list.append(&row);
+        rows.push((row, status));
+    }
+
+    let jump_button = gtk::Button::builder()
+        .label(gettext("_Go to First Losing Move"))
+        .use_underline(true)
+        .halign(gtk::Align::Center)
+        .sensitive(false)
+        .css_classes(["pill", "suggested-action"])
+        .build();
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .margin_start(12)
+        .margin_end(12)
+        .build();
+    content.append(&list);
+    content.append(&jump_button);
+    let toolbar_view = adw::ToolbarView::new();
+    toolbar_view.add_top_bar(&adw::HeaderBar::new());
+    toolbar_view.set_content(Some(
+        &gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .propagate_natural_height(true)
+            .child(&adw::Clamp::builder().child(&content).build())
+            .build(),
+    ));
+    let dialog = adw::Dialog::builder()
+        .title(gettext("Analysis"))
+        .content_width(480)
+        .content_height(560)
+        .child(&toolbar_view)
+        .build();
+
+    let mut outcomes: Vec<Option<Outcome>> = vec![None; positions.len()];
+    // The number of history entries to keep to go back to before the
+    // first losing move, and what the solver would have played instead
+    let first_losing: Rc<RefCell<Option<(usize, Vec<runtime::Move>)>>> =
+        Rc::new(RefCell::new(None));
+    jump_button.connect_clicked(glib::clone!(
+        #[weak]
+        dialog,
+        #[weak]
+        window,
+        #[strong]
+        first_losing,
+        move |_| {
+            let Some((n_entries, solution)) = first_losing.borrow().clone() else {
+                return;
+            };
+            dialog.close();
+            let game_board = window.get_gameboard();
+            runtime::rewind_to(&game_board, n_entries, solution.clone());
+            if let Some(move_) = solution.first() {
+                game_board.show_hint(move_);
+                window.show_toast(&gettext("This is what the solver would have played"));
+            }
+        }
+    ));
+
+    let should_stop = Arc::new(AtomicBool::new(false));
+    dialog.connect_closed(glib::clone!(
+        #[strong]
+        should_stop,
+        move |_| should_stop.store(true, Ordering::Relaxed)
+    ));
+    dialog.present(Some(window));
+
+    let (sender, receiver) = async_channel::unbounded();
+    std::thread::spawn(move || {
+        let stop = AtomicBool::new(false);
+        for (i, (game_state, (move_generator, mut is_won_fn))) in
+            positions.into_iter().zip(solvers).enumerate()
+        {
+            if should_stop.load(Ordering::Relaxed) {
+                return;
+            }
+            let outcome = if is_won_fn(&mut games::solver::new_ghost_state(game_state.clone())) {
+                Outcome::Winnable(Vec::new())
+            } else {
+                match games::re_solve(
+                    stack_names.clone(),
+                    game_state,
+                    move_generator,
+                    is_won_fn,
+                    &stop,
+                ) {
+                    Some(solution) if solution.is_empty() => Outcome::Unknown,
+                    Some(solution) => Outcome::Winnable(solution),
+                    None => Outcome::Lost,
+                }
+            };
+            if sender.send_blocking((i, outcome)).is_err() {
+                return;
+            }
+        }
+    });
+
+    glib::spawn_future_local(async move {
+        while let Ok((i, outcome)) = receiver.recv().await {
+            outcomes[i] = Some(outcome);
+            // The moves on either side of the position may be judged now
+            for entry in [i.checked_sub(1), Some(i)].into_iter().flatten() {
+                let (Some(Some(before)), Some(Some(after))) =
+                    (outcomes.get(entry), outcomes.get(entry + 1))
+                else {
+                    continue;
+                };
+                let (row, status) = &rows[entry];
+                let verdict = verdict(before, after, history[entry].moves().next());
+                let (label, class) = match verdict {
+                    Verdict::Good => (gettext("Good"), "success"),
+                    Verdict::Neutral => (gettext("Neutral"), "dim-label"),
+                    Verdict::Losing => (gettext("Losing"), "error"),
+                };
+                status.set_label(&label);
+                status.set_css_classes(&[class]);
+                if verdict == Verdict::Losing && first_losing.borrow().is_none() {
+                    let Outcome::Winnable(solution) = before else {
+                        continue;
+                    };
+                    first_losing.replace(Some((entry, solution.clone())));
+                    jump_button.set_sensitive(true);
+                    row.add_css_class("error");
+                }
+            }
+        }
+    });
+}
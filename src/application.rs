@@ -19,7 +19,7 @@
  */
 
 use crate::config::{APP_ID, VERSION};
-use crate::SolitaireWindow;
+use crate::{automation, games, replay, SolitaireWindow};
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 use gettextrs::gettext;
@@ -44,9 +44,13 @@ mod imp {
             let obj = self.obj();
             obj.setup_gactions();
             obj.set_accels_for_action("app.quit", &["<primary>q"]);
+            obj.set_accels_for_action("app.new-window", &["<primary>n"]);
+            obj.set_accels_for_action("app.preferences", &["<primary>comma"]);
             obj.set_accels_for_action("win.hint", &["<primary>h"]);
             obj.set_accels_for_action("win.redo", &["<primary><shift>z"]);
             obj.set_accels_for_action("win.undo", &["<primary>z"]);
+            obj.add_options();
+            games::migrate_recent_game();
         }
     }
 
@@ -66,6 +70,34 @@ mod imp {
             // Ask the window manager/compositor to present the window
             window.present();
         }
+
+        fn dbus_register(
+            &self,
+            connection: &gio::DBusConnection,
+            object_path: &str,
+        ) -> Result<(), glib::Error> {
+            self.parent_dbus_register(connection, object_path)?;
+            automation::register(&self.obj(), connection, object_path)
+        }
+
+        fn dbus_unregister(&self, connection: &gio::DBusConnection, object_path: &str) {
+            automation::unregister();
+            self.parent_dbus_unregister(connection, object_path);
+        }
+
+        // Command lines of later launches are forwarded here too, so
+        // `solitaire --game freecell` opens the game in the running instance
+        fn command_line(&self, command_line: &gio::ApplicationCommandLine) -> glib::ExitCode {
+            self.activate();
+            let application = self.obj();
+            let Some(window) = application
+                .active_window()
+                .and_downcast::<SolitaireWindow>()
+            else {
+                return glib::ExitCode::FAILURE;
+            };
+            application.handle_command_line(&window, command_line)
+        }
     }
 
     impl GtkApplicationImpl for SolitaireApplication {}
@@ -87,6 +119,138 @@ impl SolitaireApplication {
             .build()
     }
 
+    fn add_options(&self) {
+        self.add_main_option(
+            "game",
+            glib::Char::from(b'g'),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::String,
+            &gettext("Start a game of the given id, like “klondike”"),
+            Some("GAME"),
+        );
+        self.add_main_option(
+            "seed",
+            glib::Char::from(b's'),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::Int64,
+            &gettext("Deal the cards of the given number"),
+            Some("NUMBER"),
+        );
+        self.add_main_option(
+            "deal",
+            glib::Char::from(b'd'),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::Int64,
+            &gettext("Same as --seed"),
+            Some("NUMBER"),
+        );
+        self.add_main_option(
+            "replay",
+            glib::Char::from(b'r'),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::Filename,
+            &gettext("Play back a replay file"),
+            Some("FILE"),
+        );
+        self.add_main_option(
+            "position",
+            glib::Char::from(b'p'),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::Filename,
+            &gettext("Set up the game in the position written in the file"),
+            Some("FILE"),
+        );
+        self.add_main_option(
+            "no-solver-check",
+            glib::Char::from(0),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::None,
+            &gettext("Deal without checking that the game can be won"),
+            None,
+        );
+    }
+
+    fn handle_command_line(
+        &self,
+        window: &SolitaireWindow,
+        command_line: &gio::ApplicationCommandLine,
+    ) -> glib::ExitCode {
+        let options = command_line.options_dict();
+        let lookup_file = |name: &str| {
+            options
+                .lookup::<std::path::PathBuf>(name)
+                .ok()
+                .flatten()
+                .map(|path| command_line.create_file_for_arg(path))
+        };
+        if let Some(file) = lookup_file("replay") {
+            replay::open_file(window, file);
+            return glib::ExitCode::SUCCESS;
+        }
+
+        let seed = ["seed", "deal"]
+            .iter()
+            .find_map(|name| options.lookup::<i64>(name).ok().flatten());
+        let seed = match seed.map(u32::try_from) {
+            Some(Ok(seed)) => Some(seed),
+            Some(Err(_)) => {
+                command_line.printerr_literal(&format!(
+                    "{}\n",
+                    gettext("The deal number must be between 0 and {}")
+                        .replace("{}", &u32::MAX.to_string())
+                ));
+                return glib::ExitCode::FAILURE;
+            }
+            None => None,
+        };
+        let game = match options.lookup::<String>("game").ok().flatten() {
+            Some(game_id) => {
+                let Some(game) = games::get_game(&game_id) else {
+                    let game_ids = games::GAMES
+                        .iter()
+                        .map(|game| game.id)
+                        .collect::<Vec<&str>>()
+                        .join(", ");
+                    command_line.printerr_literal(&format!(
+                        "{}\n",
+                        gettext("Unknown game “{game}”, the games are: {games}")
+                            .replace("{game}", &game_id)
+                            .replace("{games}", &game_ids)
+                    ));
+                    return glib::ExitCode::FAILURE;
+                };
+                Some(game)
+            }
+            None => None,
+        };
+
+        let position = lookup_file("position");
+        let solver_check = !options.contains("no-solver-check");
+        if game.is_none() && position.is_none() && seed.is_none() && solver_check {
+            return glib::ExitCode::SUCCESS;
+        }
+        // Without a game, the position or deal is for the last game played
+        let settings = gio::Settings::new(crate::APP_ID);
+        let recent_game = settings.get::<String>("recent-game");
+        let Some(game) = game.or_else(|| games::get_game(&recent_game)) else {
+            command_line.printerr_literal(&format!(
+                "{}\n",
+                gettext("A position or deal needs a game, use --game")
+            ));
+            return glib::ExitCode::FAILURE;
+        };
+        if let Some(file) = position {
+            window.open_position(game, file);
+        } else {
+            let deal = games::Deal { seed, solver_check };
+            window.leave_game(move |window| {
+                window.show_chooser();
+                window.start_deal(game, deal);
+            });
+        }
+        glib::ExitCode::SUCCESS
+    }
+
     fn setup_gactions(&self) {
         let quit_action = gio::ActionEntry::builder("quit")
             .activate(move |app: &Self, _, _| app.quit())
@@ -94,7 +258,23 @@ impl SolitaireApplication {
         let about_action = gio::ActionEntry::builder("about")
             .activate(move |app: &Self, _, _| app.show_about())
             .build();
-        self.add_action_entries([quit_action, about_action]);
+        let preferences_action = gio::ActionEntry::builder("preferences")
+            .activate(move |app: &Self, _, _| app.show_preferences())
+            .build();
+        let new_window_action = gio::ActionEntry::builder("new-window")
+            .activate(move |app: &Self, _, _| SolitaireWindow::new(app).present())
+            .build();
+        self.add_action_entries([
+            quit_action,
+            about_action,
+            preferences_action,
+            new_window_action,
+        ]);
+    }
+
+    fn show_preferences(&self) {
+        let window = self.active_window().unwrap();
+        crate::preferences::preferences_dialog().present(Some(&window));
     }
 
     fn show_about(&self) {
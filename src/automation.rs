@@ -0,0 +1,252 @@
+/* automation.rs
+ *
+ * Copyright 2026 Will Warner
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! A D-Bus interface on the application's object path, so UI tests and
+//! accessibility tools can play the game in the active window.
+//!
+//! ```sh
+//! gdbus call --session --dest org.gnome.gitlab.wwarner.Solitaire \
+//!     --object-path /org/gnome/gitlab/wwarner/Solitaire \
+//!     --method org.gnome.gitlab.wwarner.Solitaire.Automation.StartGame klondike 11982
+//! ```
+
+use crate::{
+    application::SolitaireApplication, game_board::GameBoard, games, keyboard, player, runtime,
+    window::SolitaireWindow,
+};
+use adw::prelude::*;
+use gtk::{gio, glib};
+use std::cell::RefCell;
+
+const INTERFACE_NAME: &str = "org.gnome.gitlab.wwarner.Solitaire.Automation";
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="org.gnome.gitlab.wwarner.Solitaire.Automation">
+    <!-- Deals a game without asking to leave the one being played, Ready
+         follows when the cards are on the board -->
+    <method name="StartGame">
+      <arg type="s" name="game_id" direction="in"/>
+      <arg type="u" name="seed" direction="in"/>
+    </method>
+    <!-- The position in the notation of Copy Position -->
+    <method name="GetBoard">
+      <arg type="s" name="position" direction="out"/>
+    </method>
+    <!-- Moves the card and the cards on top of it, if the game allows it -->
+    <method name="Move">
+      <arg type="s" name="origin" direction="in"/>
+      <arg type="s" name="card" direction="in"/>
+      <arg type="s" name="destination" direction="in"/>
+      <arg type="b" name="moved" direction="out"/>
+    </method>
+    <!-- Clicks a stack, like the stock to deal more cards -->
+    <method name="ClickStack">
+      <arg type="s" name="stack" direction="in"/>
+    </method>
+    <method name="Undo"/>
+    <method name="Redo"/>
+    <!-- Shows the next hint and returns its move -->
+    <method name="Hint">
+      <arg type="s" name="origin" direction="out"/>
+      <arg type="s" name="card" direction="out"/>
+      <arg type="s" name="destination" direction="out"/>
+    </method>
+    <signal name="Moved">
+      <arg type="s" name="origin"/>
+      <arg type="s" name="card"/>
+      <arg type="s" name="destination"/>
+    </signal>
+    <!-- The board takes input again after a deal, move, undo or redo -->
+    <signal name="Ready"/>
+    <signal name="Won"/>
+  </interface>
+</node>
+"#;
+
+struct Registration {
+    connection: gio::DBusConnection,
+    object_path: String,
+    id: gio::RegistrationId,
+}
+
+thread_local! {
+    static REGISTRATION: RefCell<Option<Registration>> = const { RefCell::new(None) };
+}
+
+pub fn register(
+    application: &SolitaireApplication,
+    connection: &gio::DBusConnection,
+    object_path: &str,
+) -> Result<(), glib::Error> {
+    let node = gio::DBusNodeInfo::for_xml(INTERFACE_XML)?;
+    let interface = node
+        .lookup_interface(INTERFACE_NAME)
+        .expect("Automation interface is missing");
+    let id = connection
+        .register_object(object_path, &interface)
+        .method_call(glib::clone!(
+            #[weak]
+            application,
+            move |_, _, _, _, method, parameters, invocation| {
+                invocation.return_result(call(&application, method, &parameters));
+            }
+        ))
+        .build()?;
+    REGISTRATION.set(Some(Registration {
+        connection: connection.clone(),
+        object_path: object_path.to_string(),
+        id,
+    }));
+    Ok(())
+}
+
+pub fn unregister() {
+    if let Some(registration) = REGISTRATION.take() {
+        if let Err(error) = registration.connection.unregister_object(registration.id) {
+            glib::g_warning!("solitaire", "Failed to unregister automation: {error}");
+        }
+    }
+}
+
+fn emit(signal: &str, parameters: Option<&glib::Variant>) {
+    REGISTRATION.with_borrow(|registration| {
+        let Some(registration) = registration else {
+            return;
+        };
+        if let Err(error) = registration.connection.emit_signal(
+            None,
+            &registration.object_path,
+            INTERFACE_NAME,
+            signal,
+            parameters,
+        ) {
+            glib::g_warning!("solitaire", "Failed to emit {signal}: {error}");
+        }
+    });
+}
+
+pub fn emit_moved(move_: &runtime::Move) {
+    let parameters = (
+        move_.origin_stack.clone(),
+        move_.card_name.clone(),
+        move_.destination_stack.clone(),
+    )
+        .to_variant();
+    emit("Moved", Some(&parameters));
+}
+
+pub fn emit_ready() {
+    emit("Ready", None);
+}
+
+pub fn emit_won() {
+    emit("Won", None);
+}
+
+fn failed(message: &str) -> glib::Error {
+    glib::Error::new(gio::IOErrorEnum::Failed, message)
+}
+
+fn invalid_args() -> glib::Error {
+    glib::Error::new(gio::DBusError::InvalidArgs, "Invalid arguments")
+}
+
+fn call(
+    application: &SolitaireApplication,
+    method: &str,
+    parameters: &glib::Variant,
+) -> Result<Option<glib::Variant>, glib::Error> {
+    let window = application
+        .active_window()
+        .and_downcast::<SolitaireWindow>()
+        .ok_or_else(|| failed("No window is open"))?;
+    let game_board = window.get_gameboard();
+    if method == "StartGame" {
+        let (game_id, seed) = parameters.get::<(String, u32)>().ok_or_else(invalid_args)?;
+        let game = games::get_game(&game_id).ok_or_else(|| failed("Unknown game"))?;
+        games::unload(&game_board);
+        game_board.session().set_should_stop(true);
+        window.show_chooser();
+        window.start_deal(
+            game,
+            games::Deal {
+                seed: Some(seed),
+                ..games::Deal::default()
+            },
+        );
+        return Ok(None);
+    }
+
+    if game_board.session().game.borrow().is_none() {
+        return Err(failed("No game is being played"));
+    }
+    // Replays and the solution player make their own moves
+    if matches!(method, "Move" | "ClickStack" | "Undo" | "Redo")
+        && (runtime::is_busy(&game_board) || player::is_replay(&game_board))
+    {
+        return Err(failed("The board is busy, wait for Ready"));
+    }
+    match method {
+        "GetBoard" => {
+            let (stack_names, game_state) = runtime::get_solver_state(&game_board);
+            let position = games::notation::format_position(&stack_names, &game_state);
+            Ok(Some((position,).to_variant()))
+        }
+        "Move" => {
+            let (origin, card, destination) = parameters
+                .get::<(String, String, String)>()
+                .ok_or_else(invalid_args)?;
+            player::pause(&game_board);
+            let moved = find_card(&game_board, &origin, &card)
+                .zip(runtime::get_stack(&game_board, &destination))
+                .is_some_and(|(card, destination)| keyboard::move_cards(&card, &destination));
+            Ok(Some((moved,).to_variant()))
+        }
+        "ClickStack" => {
+            let (stack,) = parameters.get::<(String,)>().ok_or_else(invalid_args)?;
+            let stack =
+                runtime::get_stack(&game_board, &stack).ok_or_else(|| failed("Unknown stack"))?;
+            player::pause(&game_board);
+            games::stack_click(&stack);
+            Ok(None)
+        }
+        "Undo" | "Redo" => {
+            WidgetExt::activate_action(&window, &format!("win.{}", method.to_lowercase()), None)
+                .map_err(|error| failed(&error.to_string()))?;
+            Ok(None)
+        }
+        "Hint" => {
+            let move_ = runtime::next_hint(&game_board).ok_or_else(|| failed("No hints"))?;
+            game_board.show_hint(&move_);
+            Ok(Some(
+                (move_.origin_stack, move_.card_name, move_.destination_stack).to_variant(),
+            ))
+        }
+        _ => Err(glib::Error::new(
+            gio::DBusError::UnknownMethod,
+            &format!("Unknown method {method}"),
+        )),
+    }
+}
+
+fn find_card(game_board: &GameBoard, stack: &str, card: &str) -> Option<crate::card::Card> {
+    let stack = runtime::get_stack(game_board, stack)?;
+    runtime::get_child(&stack, card).ok()?.downcast().ok()
+}
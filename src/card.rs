@@ -18,8 +18,9 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use crate::{card_stack::CardStack, games, renderer};
+use crate::{card_stack::CardStack, games};
 use adw::{prelude::*, subclass::prelude::*};
+use gettextrs::gettext;
 use gtk::prelude::{Cast, WidgetExt};
 use gtk::{gdk, glib};
 use std::cell::Cell;
@@ -35,7 +36,8 @@ mod imp {
 
     #[derive(Default)]
     pub struct Card {
-        pub texture: Cell<Option<gdk::MemoryTexture>>,
+        pub texture: Cell<Option<gdk::Texture>>,
+        pub back_texture: Cell<Option<gdk::Texture>>,
         pub is_face_up: Cell<bool>,
         pub card_id: Cell<u8>,
     }
@@ -67,78 +69,104 @@ mod imp {
 }
 
 impl Card {
-    pub fn new(
-        name: &str,
-        id: i32,
-        renderer: &rsvg::CairoRenderer,
-        card_theme: &renderer::CardTheme,
-    ) -> Self {
+    pub fn new(name: &str, id: i32) -> Self {
         let this: Card = glib::Object::new();
         this.set_sensitive(true);
         this.set_can_focus(true);
         this.set_widget_name(name);
         this.imp().card_id.set(id as u8);
         let picture = gtk::Picture::new();
-        let texture = renderer::draw_card(name, renderer, &card_theme, id % 13, id / 13);
-        picture.set_paintable(Some(&texture));
         picture.set_focusable(false);
         this.set_child(Some(&picture));
-        this.imp().texture.set(Some(texture));
         this.imp().is_face_up.set(true);
 
         this.set_focusable(true);
         this.set_accessible_role(gtk::AccessibleRole::ListItem);
-        this.update_property(&[gtk::accessible::Property::Description(
-            &name.replace("_", " "),
-        )]);
+        this.update_label();
         this.add_css_class("no-padding");
 
         this
     }
 
-    pub fn flip(&self) {
-        let is_face_up = self.imp().is_face_up.get();
+    /// Swaps the images of the card, for example after rendering them at another size
+    pub fn set_textures(&self, face: &gdk::Texture, back: &gdk::Texture) {
+        self.imp().texture.set(Some(face.clone()));
+        self.imp().back_texture.set(Some(back.clone()));
+        self.show_side();
+    }
+
+    fn show_side(&self) {
         let picture = self.child().unwrap().downcast::<gtk::Picture>().unwrap();
-        if is_face_up {
-            if let Some(back_texture) = renderer::BACK_TEXTURE.with(|t| t.borrow().to_owned()) {
-                picture.set_paintable(Some(&back_texture));
-            } else {
-                glib::g_critical!("solitaire", "Tried to flip a card with no back texture");
-            }
+        let texture = if self.imp().is_face_up.get() {
+            &self.imp().texture
         } else {
-            if let Some(face_texture) = self.imp().texture.take() {
-                picture.set_paintable(Some(&face_texture));
-                self.imp().texture.set(Some(face_texture));
-            } else {
-                glib::g_critical!("solitaire", "Tried to flip a card with no face texture");
-            }
+            &self.imp().back_texture
+        };
+        let paintable = texture.take();
+        picture.set_paintable(paintable.as_ref());
+        texture.set(paintable);
+        self.update_label();
+        if let Some(stack) = self.stack() {
+            stack.update_summary();
+        }
+    }
+
+    fn update_label(&self) {
+        self.update_property(&[gtk::accessible::Property::Label(&self.spoken_label())]);
+    }
+
+    /// What screen readers say for the card, hiding it while it is face down
+    pub fn spoken_label(&self) -> String {
+        if self.is_face_up() {
+            self.spoken_name()
+        } else {
+            gettext("Face-down card")
         }
-        self.imp().is_face_up.set(!is_face_up);
+    }
+
+    /// The name of the card as screen readers say it, like “Queen of Hearts”
+    pub fn spoken_name(&self) -> String {
+        let id = self.id();
+        let rank = match id % 13 {
+            0 => gettext("Ace"),
+            10 => gettext("Jack"),
+            11 => gettext("Queen"),
+            12 => gettext("King"),
+            rank => (rank + 1).to_string(),
+        };
+        let suit = match id / 13 {
+            0 => gettext("Clubs"),
+            1 => gettext("Diamonds"),
+            2 => gettext("Hearts"),
+            _ => gettext("Spades"),
+        };
+        // Translators: a playing card, like “Queen of Hearts”
+        gettext("{rank} of {suit}")
+            .replace("{rank}", &rank)
+            .replace("{suit}", &suit)
+    }
+
+    pub fn flip(&self) {
+        self.imp().is_face_up.set(!self.imp().is_face_up.get());
+        self.show_side();
     }
 
     pub fn flip_to_face(&self) {
-        let is_face_up = self.imp().is_face_up.get();
-        let picture = self.child().unwrap().downcast::<gtk::Picture>().unwrap();
-        if !is_face_up {
-            if let Some(face_texture) = self.imp().texture.take() {
-                picture.set_paintable(Some(&face_texture));
-                self.imp().texture.set(Some(face_texture));
-            }
-            self.imp().is_face_up.set(true);
+        if !self.imp().is_face_up.get() {
+            self.flip();
         }
     }
 
     pub fn flip_to_back(&self) {
-        let is_face_up = self.imp().is_face_up.get();
-        let picture = self.child().unwrap().downcast::<gtk::Picture>().unwrap();
-        if is_face_up {
-            if let Some(back_texture) = renderer::BACK_TEXTURE.with(|t| t.borrow().to_owned()) {
-                picture.set_paintable(Some(&back_texture));
-            }
-            self.imp().is_face_up.set(false);
+        if self.imp().is_face_up.get() {
+            self.flip();
         }
     }
 
+    pub fn id(&self) -> usize {
+        self.imp().card_id.get() as usize
+    }
+
     pub fn is_one_rank_above(&self, lower_card: &Card) -> bool {
         let self_rank = self.imp().card_id.get() % 13;
         let lower_rank = lower_card.imp().card_id.get() % 13;
@@ -167,4 +195,8 @@ impl Card {
     pub fn is_face_up(&self) -> bool {
         self.imp().is_face_up.get()
     }
+
+    pub fn paintable(&self) -> Option<gdk::Paintable> {
+        self.child()?.downcast::<gtk::Picture>().ok()?.paintable()
+    }
 }
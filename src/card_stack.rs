@@ -18,10 +18,11 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use crate::{card::Card, games, renderer, runtime};
-use gtk::{gdk, gio, glib, DragSource, GestureClick};
+use crate::{card::Card, game_board::GameBoard, games, renderer, runtime};
+use gettextrs::{gettext, ngettext};
+use gtk::{gdk, gio, glib, graphene, DragSource, GestureClick};
 use gtk::{prelude::*, subclass::prelude::*};
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 
 glib::wrapper! {
     pub struct CardStack(ObjectSubclass<imp::CardStack>)
@@ -65,6 +66,7 @@ mod imp {
         pub fan_cards: Cell<bool>,
         pub v_offset: Cell<u32>,
         pub stack_type: Cell<String>,
+        pub n_of_type: Cell<i32>,
     }
 
     #[glib::object_subclass]
@@ -87,7 +89,6 @@ mod imp {
 
             this.set_focusable(true);
             this.set_accessible_role(gtk::AccessibleRole::List);
-            this.update_property(&[gtk::accessible::Property::Description(&this.stack_type())]);
         }
     }
 
@@ -218,9 +219,13 @@ mod imp {
     #[derive(Default)]
     pub struct TransferCardStack {
         pub origin_name: Cell<String>,
+        // While dragged, the transfer stack lives in a drag icon, outside of any board
+        pub origin_stack: glib::WeakRef<super::CardStack>,
         pub v_offset: Cell<u32>,
         pub drag_x: Cell<i32>,
         pub drag_y: Cell<i32>,
+        // The cards a probe stands in for, still on their stack
+        pub probed: RefCell<Vec<Card>>,
     }
 
     #[glib::object_subclass]
@@ -273,21 +278,68 @@ impl CardStack {
         let this: CardStack = glib::Object::new();
         this.imp().stack_type.set(stack_type.to_string());
         this.imp().fan_cards.set(fan_cards);
+        this.imp().n_of_type.set(n_of_type);
         if n_of_type < 0 {
             this.set_widget_name(stack_type);
         } else {
             this.set_widget_name(&*format!("{}_{}", stack_type, n_of_type));
         }
 
-        runtime::add_stack(&*this.widget_name(), &this);
-
         let placeholder = gtk::Picture::new();
         placeholder.add_css_class("stack-placeholder");
         placeholder.insert_before(&this, None::<&gtk::Widget>);
+        this.update_summary();
 
         this
     }
 
+    /// The name of the stack as screen readers say it, like “Tableau 3”
+    pub fn spoken_name(&self) -> String {
+        let kind = match &*self.stack_type() {
+            "tableau" => gettext("Tableau"),
+            "foundation" => gettext("Foundation"),
+            "stock" => gettext("Stock"),
+            "waste" => gettext("Waste"),
+            "cell" => gettext("Free Cell"),
+            "pyramid" => gettext("Pyramid"),
+            other => other.to_string(),
+        };
+        match self.imp().n_of_type.get() {
+            n if n < 0 => kind,
+            n => format!("{kind} {}", n + 1),
+        }
+    }
+
+    /// Updates what screen readers say about the stack, like
+    /// “Tableau 3, 5 cards, top: 7 of Clubs”
+    pub fn update_summary(&self) {
+        let n_cards = self.n_cards();
+        let summary = match self.last_card() {
+            Some(card) => {
+                let top = card.spoken_label();
+                // Translators: a stack of cards, like “Tableau 3, 5 cards, top: 7 of Clubs”
+                gettext("{stack}, {cards}, top: {card}")
+                    .replace("{stack}", &self.spoken_name())
+                    .replace(
+                        "{cards}",
+                        &ngettext("{} card", "{} cards", n_cards as u32)
+                            .replace("{}", &n_cards.to_string()),
+                    )
+                    .replace("{card}", &top)
+            }
+            // Translators: a stack without cards, like “Tableau 3, empty”
+            None => gettext("{stack}, empty").replace("{stack}", &self.spoken_name()),
+        };
+        self.update_property(&[gtk::accessible::Property::Label(&summary)]);
+    }
+
+    /// The board this stack was added to by its game
+    pub fn game_board(&self) -> GameBoard {
+        self.parent()
+            .and_downcast::<GameBoard>()
+            .expect("CardStack is not on a GameBoard")
+    }
+
     pub fn stack_type(&self) -> String {
         let value = self.imp().stack_type.take();
         self.imp().stack_type.set(value.clone());
@@ -300,26 +352,7 @@ impl CardStack {
         drop_target.connect_drop(|drop, val, _x, _y| {
             let to_stack = drop.widget().unwrap().downcast::<CardStack>().unwrap();
             if let Ok(transfer_stack) = val.get::<TransferCardStack>() {
-                let first_card = transfer_stack.first_card();
-                if games::verify_drop(&transfer_stack, &to_stack) {
-                    to_stack.merge_stack(&transfer_stack);
-                    //FIXME: do not use widget_name
-                    let mut move_ = runtime::create_move(
-                        &transfer_stack.get_origin_name(),
-                        &first_card.widget_name(),
-                        &to_stack.widget_name(),
-                        runtime::MoveInstruction::None,
-                    );
-                    games::drag_completed(
-                        &runtime::get_stack(&*transfer_stack.get_origin_name()).unwrap(),
-                        &to_stack,
-                        &mut move_,
-                    );
-                    runtime::add_to_history(move_);
-                    return true;
-                } else {
-                    return false;
-                }
+                to_stack.drop_stack(&transfer_stack)
             } else {
                 let type_name = drop.value_type().to_string();
                 glib::g_warning!(
@@ -332,6 +365,34 @@ impl CardStack {
         self.add_controller(drop_target);
     }
 
+    /// Puts the cards of a transfer stack on this stack and records the move,
+    /// if the game allows it
+    pub fn drop_stack(&self, transfer_stack: &TransferCardStack) -> bool {
+        let first_card = transfer_stack.first_card();
+        if !games::verify_drop(transfer_stack, self) {
+            // Translators: a move that isn't allowed, like “Can't move 7 of Clubs to Tableau 3”
+            let message = gettext("Can't move {card} to {stack}")
+                .replace("{card}", &first_card.spoken_name())
+                .replace("{stack}", &self.spoken_name());
+            self.announce(&message, gtk::AccessibleAnnouncementPriority::Medium);
+            return false;
+        }
+        let game_board = self.game_board();
+        self.merge_stack(transfer_stack);
+        //FIXME: do not use widget_name
+        let move_ = runtime::create_move(
+            &transfer_stack.get_origin_name(),
+            &first_card.widget_name(),
+            &self.widget_name(),
+            runtime::MoveInstruction::None,
+        );
+        runtime::start_transaction(&game_board);
+        runtime::add_to_history(&game_board, &move_);
+        games::drag_completed(&transfer_stack.origin_stack().unwrap(), self, &move_);
+        runtime::end_transaction(&game_board);
+        true
+    }
+
     pub fn add_click(&self) {
         let click = GestureClick::new();
         let stack_clone = self.clone();
@@ -351,6 +412,7 @@ impl CardStack {
             .imp()
             .origin_name
             .set(self.widget_name().to_string());
+        new_stack.imp().origin_stack.set(Some(self));
 
         // First, find the starting index
         let start_index = child_index(card_name, &children).expect("Couldn't get card");
@@ -391,6 +453,7 @@ impl CardStack {
     pub fn add_card(&self, card: &Card) {
         if card.parent().is_none() {
             card.insert_before(self, None::<&gtk::Widget>);
+            self.update_summary();
         } else {
             glib::g_warning!(
                 "solitaire",
@@ -434,24 +497,6 @@ impl CardStack {
         }
     }
 
-    pub fn face_up_top_card(&self) {
-        if let Some(widget) = self.last_card() {
-            let card = widget
-                .downcast::<Card>()
-                .expect("Child is not a Card (flip)");
-            card.flip_to_face();
-        }
-    }
-
-    pub fn face_down_top_card(&self) {
-        if let Some(widget) = self.last_card() {
-            let card = widget
-                .downcast::<Card>()
-                .expect("Child is not a Card (flip)");
-            card.flip_to_back();
-        }
-    }
-
     pub fn add_drag_to_card(&self, card: &Card) {
         let drag_source = DragSource::builder().actions(gdk::DragAction::MOVE).build();
 
@@ -492,6 +537,19 @@ impl CardStack {
                     original_stack.imp().drag_x.get(),
                     original_stack.imp().drag_y.get(),
                 );
+                original_stack.show_drop_targets();
+            }
+        });
+
+        drag_source.connect_drag_end(|src, _drag, _delete_data| {
+            let stack = src
+                .widget()
+                .and_downcast::<Card>()
+                .and_then(|card| card.stack());
+            if let Some(stack) = stack {
+                for target in stack.game_board().stacks() {
+                    target.remove_css_class("drop-target");
+                }
             }
         });
 
@@ -499,7 +557,8 @@ impl CardStack {
             let provider = drag.content();
             let value = provider.value(TransferCardStack::static_type()).unwrap();
             if let Ok(drag_stack) = value.get::<TransferCardStack>() {
-                let origin = runtime::get_stack(&*drag_stack.get_origin_name())
+                let origin = drag_stack
+                    .origin_stack()
                     .expect("drag_recovery: Failed to get origin stack");
                 origin.merge_stack(&drag_stack);
             }
@@ -537,8 +596,35 @@ impl CardStack {
         card.add_css_class("highlight");
     }
 
+    /// Where a card put on this stack would be drawn, in the coordinates of `target`
+    pub fn drop_bounds(&self, target: &impl IsA<gtk::Widget>) -> Option<graphene::Rect> {
+        let Some(card) = self.last_card() else {
+            let bounds = self.compute_bounds(target)?;
+            let height = bounds.width() * renderer::ASPECT.get();
+            return Some(graphene::Rect::new(
+                bounds.x(),
+                bounds.y(),
+                bounds.width(),
+                height,
+            ));
+        };
+        let bounds = card.compute_bounds(target)?;
+        let offset = if self.imp().fan_cards.get() {
+            bounds.height() / 5.0
+        } else {
+            0.0
+        };
+        Some(graphene::Rect::new(
+            bounds.x(),
+            bounds.y() + offset,
+            bounds.width(),
+            bounds.height(),
+        ))
+    }
+
     pub fn remove_card(&self, card: &Card) {
         card.unparent();
+        self.update_summary();
     }
 
     pub fn is_empty(&self) -> bool {
@@ -570,6 +656,23 @@ impl TransferCardStack {
         glib::Object::new()
     }
 
+    /// Stands in for `card` and the cards on top of it without taking them
+    /// off `origin`, to ask the game where they could be dropped
+    pub fn probe(origin: &CardStack, card: &Card) -> Self {
+        let probe = Self::new();
+        probe
+            .imp()
+            .origin_name
+            .set(origin.widget_name().to_string());
+        probe.imp().origin_stack.set(Some(origin));
+        let cards = (1..=origin.n_cards())
+            .filter_map(|i| origin.get_card(i))
+            .skip_while(|other| other != card)
+            .collect();
+        probe.imp().probed.replace(cards);
+        probe
+    }
+
     pub fn add_card(&self, card: &Card) {
         if card.parent().is_none() {
             card.insert_before(self, None::<&gtk::Widget>);
@@ -582,34 +685,63 @@ impl TransferCardStack {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.last_child().is_none()
+        self.n_cards() == 0
     }
 
     pub fn last_card(&self) -> Card {
+        if let Some(card) = self.imp().probed.borrow().last() {
+            return card.clone();
+        }
         self.last_child().unwrap().downcast::<Card>().unwrap()
     }
 
     pub fn first_card(&self) -> Card {
+        if let Some(card) = self.imp().probed.borrow().first() {
+            return card.clone();
+        }
         self.first_child().unwrap().downcast::<Card>().unwrap()
     }
 
     pub fn n_cards(&self) -> usize {
+        let probed = self.imp().probed.borrow().len();
+        if probed > 0 {
+            return probed;
+        }
         self.observe_children().n_items() as usize
     }
 
     pub fn get_card(&self, index: usize) -> Option<Card> {
+        if let Some(card) = self.imp().probed.borrow().get(index) {
+            return Some(card.clone());
+        }
         self.observe_children()
             .item(index as u32)?
             .downcast::<Card>()
             .ok()
     }
 
+    /// Marks the stacks these cards may be dropped on
+    pub fn show_drop_targets(&self) {
+        let Some(origin) = self.origin_stack() else {
+            return;
+        };
+        for stack in origin.game_board().stacks() {
+            if stack != origin && games::verify_drop(self, &stack) {
+                stack.add_css_class("drop-target");
+            }
+        }
+    }
+
     pub fn get_origin_name(&self) -> String {
         let name = self.imp().origin_name.take();
         self.imp().origin_name.set(name.clone());
         name
     }
 
+    pub fn origin_stack(&self) -> Option<CardStack> {
+        self.imp().origin_stack.upgrade()
+    }
+
     pub fn remove_card(&self, card: &Card) {
         card.unparent();
     }
@@ -0,0 +1,314 @@
+/* celebration.rs
+ *
+ * Copyright 2026 Will Warner
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use crate::{card::Card, card_stack::CardStack, game_board::GameBoard};
+use gtk::prelude::*;
+use gtk::{gdk, glib, graphene};
+
+// Trail stamps are flattened into one texture once there are this many
+const MAX_STAMPS: usize = 64;
+// Fractions of the board size per second (squared)
+const GRAVITY: f32 = 2.5;
+const MIN_SPEED: f32 = 0.2;
+const MAX_SPEED: f32 = 0.4;
+const BOUNCINESS: f32 = 0.7;
+
+struct Bouncer {
+    card: Card,
+    paintable: gdk::Paintable,
+    bounds: graphene::Rect,
+    velocity: (f32, f32),
+}
+
+/// Cards bouncing off the foundations one at a time, leaving a trail behind,
+/// after a game has been won
+pub struct Celebration {
+    waiting: Vec<Card>,
+    launched: Vec<Card>,
+    bouncer: Option<Bouncer>,
+    trail: Option<(gdk::Texture, graphene::Rect)>,
+    stamps: Vec<(gdk::Paintable, graphene::Rect)>,
+    last_frame: Option<i64>,
+    tick: Option<gtk::TickCallbackId>,
+    controllers: Vec<gtk::EventController>,
+    on_done: Option<Box<dyn FnOnce()>>,
+}
+
+impl Celebration {
+    pub fn snapshot(&self, snapshot: &gtk::Snapshot) {
+        if let Some((texture, bounds)) = &self.trail {
+            snapshot.append_texture(texture, bounds);
+        }
+        for (paintable, bounds) in &self.stamps {
+            draw(snapshot, paintable, bounds);
+        }
+        if let Some(bouncer) = &self.bouncer {
+            draw(snapshot, &bouncer.paintable, &bouncer.bounds);
+        }
+    }
+
+    fn flatten_trail(&mut self, game_board: &GameBoard) {
+        let Some(renderer) = game_board.native().and_then(|native| native.renderer()) else {
+            return;
+        };
+        let scale = game_board.scale_factor() as f32;
+        let bounds = graphene::Rect::new(
+            0.0,
+            0.0,
+            game_board.width() as f32,
+            game_board.height() as f32,
+        );
+        let snapshot = gtk::Snapshot::new();
+        snapshot.scale(scale, scale);
+        if let Some((texture, bounds)) = &self.trail {
+            snapshot.append_texture(texture, bounds);
+        }
+        for (paintable, bounds) in &self.stamps {
+            draw(&snapshot, paintable, bounds);
+        }
+        let Some(node) = snapshot.to_node() else {
+            return;
+        };
+        let viewport =
+            graphene::Rect::new(0.0, 0.0, bounds.width() * scale, bounds.height() * scale);
+        self.trail = Some((renderer.render_texture(node, Some(&viewport)), bounds));
+        self.stamps.clear();
+    }
+}
+
+fn draw(snapshot: &gtk::Snapshot, paintable: &gdk::Paintable, bounds: &graphene::Rect) {
+    snapshot.save();
+    snapshot.translate(&graphene::Point::new(bounds.x(), bounds.y()));
+    paintable.snapshot(snapshot, bounds.width() as f64, bounds.height() as f64);
+    snapshot.restore();
+}
+
+// Cards leave the foundations top first, taking turns between foundations.
+// Games without foundations send off every card on the board.
+fn launch_order(game_board: &GameBoard) -> Vec<Card> {
+    let mut stacks: Vec<CardStack> = Vec::new();
+    let mut child = game_board.first_child();
+    while let Some(widget) = child {
+        child = widget.next_sibling();
+        if let Ok(stack) = widget.downcast::<CardStack>() {
+            stacks.push(stack);
+        }
+    }
+    if stacks
+        .iter()
+        .any(|stack| stack.stack_type() == "foundation")
+    {
+        stacks.retain(|stack| stack.stack_type() == "foundation");
+    }
+    let mut piles: Vec<Vec<Card>> = stacks
+        .iter()
+        .map(|stack| {
+            let mut cards = Vec::new();
+            let mut child = stack.first_child();
+            while let Some(widget) = child {
+                child = widget.next_sibling();
+                if let Ok(card) = widget.downcast::<Card>() {
+                    cards.push(card);
+                }
+            }
+            cards
+        })
+        .collect();
+    let mut order = Vec::new();
+    while piles.iter().any(|pile| !pile.is_empty()) {
+        for pile in piles.iter_mut() {
+            if let Some(card) = pile.pop() {
+                order.push(card);
+            }
+        }
+    }
+    // Popped from the back when launching
+    order.reverse();
+    order
+}
+
+/// Plays the celebration and calls `on_done` once it is over or skipped.
+/// Without animations `on_done` is called right away.
+pub fn start(game_board: &GameBoard, on_done: impl FnOnce() + 'static) {
+    cancel(game_board);
+    if game_board.animation_duration() == 0 {
+        on_done();
+        return;
+    }
+    game_board.finish_animations();
+
+    let mut controllers = Vec::new();
+    if let Some(root) = game_board.root() {
+        let click = gtk::GestureClick::new();
+        click.set_propagation_phase(gtk::PropagationPhase::Capture);
+        click.connect_pressed(glib::clone!(
+            #[weak]
+            game_board,
+            move |gesture, _, _, _| {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                // Controllers can't be removed while they handle an event
+                glib::idle_add_local_once(move || stop(&game_board));
+            }
+        ));
+        let keys = gtk::EventControllerKey::new();
+        keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        keys.connect_key_pressed(glib::clone!(
+            #[weak]
+            game_board,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, _, _, _| {
+                glib::idle_add_local_once(move || stop(&game_board));
+                glib::Propagation::Stop
+            }
+        ));
+        let click = click.upcast::<gtk::EventController>();
+        let keys = keys.upcast::<gtk::EventController>();
+        root.add_controller(click.clone());
+        root.add_controller(keys.clone());
+        controllers.extend([click, keys]);
+    }
+
+    let tick = game_board.add_tick_callback(|game_board, frame_clock| {
+        if step(game_board, frame_clock) {
+            glib::ControlFlow::Continue
+        } else {
+            glib::ControlFlow::Break
+        }
+    });
+    game_board.celebration().replace(Some(Celebration {
+        waiting: launch_order(game_board),
+        launched: Vec::new(),
+        bouncer: None,
+        trail: None,
+        stamps: Vec::new(),
+        last_frame: None,
+        tick: Some(tick),
+        controllers,
+        on_done: Some(Box::new(on_done)),
+    }));
+}
+
+/// Ends the celebration early, or not at all if it is not running
+pub fn stop(game_board: &GameBoard) {
+    let Some(mut celebration) = game_board.celebration().take() else {
+        return;
+    };
+    if let Some(tick) = celebration.tick.take() {
+        tick.remove();
+    }
+    if let Some(root) = game_board.root() {
+        for controller in &celebration.controllers {
+            root.remove_controller(controller);
+        }
+    }
+    if let Some(bouncer) = celebration.bouncer.take() {
+        celebration.launched.push(bouncer.card);
+    }
+    for card in &celebration.launched {
+        card.set_opacity(1.0);
+    }
+    game_board.queue_draw();
+    if let Some(on_done) = celebration.on_done.take() {
+        on_done();
+    }
+}
+
+/// Like `stop`, without calling back
+pub fn cancel(game_board: &GameBoard) {
+    if let Some(celebration) = game_board.celebration().borrow_mut().as_mut() {
+        celebration.on_done = None;
+    }
+    stop(game_board);
+}
+
+// Moves the bouncing card along, returns whether to keep going
+fn step(game_board: &GameBoard, frame_clock: &gdk::FrameClock) -> bool {
+    let width = game_board.width() as f32;
+    let height = game_board.height() as f32;
+    let mut celebration = game_board.celebration().borrow_mut();
+    let Some(celebration) = celebration.as_mut() else {
+        return false;
+    };
+    let now = frame_clock.frame_time();
+    let dt = celebration
+        .last_frame
+        .map_or(0.0, |last| (now - last) as f32 / 1_000_000.0)
+        .min(0.05);
+    celebration.last_frame = Some(now);
+
+    if celebration.bouncer.is_none() {
+        while let Some(card) = celebration.waiting.pop() {
+            let (Some(bounds), Some(paintable)) =
+                (card.compute_bounds(game_board), card.paintable())
+            else {
+                continue;
+            };
+            let direction = if glib::random_int_range(0, 2) == 0 {
+                -1.0
+            } else {
+                1.0
+            };
+            let speed = glib::random_double_range(MIN_SPEED as f64, MAX_SPEED as f64) as f32;
+            let lift = glib::random_double_range(0.0, MAX_SPEED as f64) as f32;
+            card.set_opacity(0.0);
+            celebration.bouncer = Some(Bouncer {
+                card,
+                paintable,
+                bounds,
+                velocity: (direction * speed * width, -lift * height),
+            });
+            break;
+        }
+    }
+    let Some(bouncer) = celebration.bouncer.as_mut() else {
+        // Every card is gone
+        celebration.tick = None;
+        glib::idle_add_local_once(glib::clone!(
+            #[weak]
+            game_board,
+            move || stop(&game_board)
+        ));
+        return false;
+    };
+
+    let (mut x, mut y) = (bouncer.bounds.x(), bouncer.bounds.y());
+    let (w, h) = (bouncer.bounds.width(), bouncer.bounds.height());
+    bouncer.velocity.1 += GRAVITY * height * dt;
+    x += bouncer.velocity.0 * dt;
+    y += bouncer.velocity.1 * dt;
+    if y + h > height {
+        y = height - h;
+        bouncer.velocity.1 *= -BOUNCINESS;
+    }
+    bouncer.bounds = graphene::Rect::new(x, y, w, h);
+    let stamp = (bouncer.paintable.clone(), bouncer.bounds);
+    if x + w < 0.0 || x > width {
+        let bouncer = celebration.bouncer.take().unwrap();
+        celebration.launched.push(bouncer.card);
+    } else {
+        celebration.stamps.push(stamp);
+        if celebration.stamps.len() >= MAX_STAMPS {
+            celebration.flatten_trail(game_board);
+        }
+    }
+    game_board.queue_draw();
+    true
+}
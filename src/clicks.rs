@@ -0,0 +1,118 @@
+/* clicks.rs
+ *
+ * Copyright 2026 Will Warner
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use crate::{
+    card::Card,
+    card_stack::{CardStack, TransferCardStack},
+    game_board::GameBoard,
+    games, keyboard,
+};
+use gettextrs::gettext;
+use gtk::gio;
+use gtk::prelude::*;
+
+/// Lets cards be moved without dragging them, as chosen in the click-action setting
+pub fn add_controller(game_board: &GameBoard) {
+    let click = gtk::GestureClick::new();
+    // Released rather than pressed, so that clicks that start a drag are left alone
+    click.connect_released(|click, n_press, x, y| {
+        if n_press != 1 {
+            return;
+        }
+        if let Some(game_board) = click.widget().and_downcast::<GameBoard>() {
+            clicked(&game_board, x, y);
+        }
+    });
+    game_board.add_controller(click);
+}
+
+/// Whether a single click moves a card, which takes the place of double-clicking it
+pub fn is_smart_move() -> bool {
+    let settings = gio::Settings::new(crate::APP_ID);
+    settings.string("click-action") == "smart-move"
+}
+
+fn clicked(game_board: &GameBoard, x: f64, y: f64) {
+    let settings = gio::Settings::new(crate::APP_ID);
+    let action = settings.string("click-action");
+    if action == "none" {
+        return;
+    }
+    let Some(widget) = game_board.pick(x, y, gtk::PickFlags::DEFAULT) else {
+        return;
+    };
+    // What was hit is the picture of a card or the placeholder of a stack
+    let card = widget.ancestor(Card::static_type()).and_downcast::<Card>();
+    let Some(stack) = widget
+        .ancestor(CardStack::static_type())
+        .and_downcast::<CardStack>()
+    else {
+        return;
+    };
+    // The stock has its own click to deal
+    if stack.stack_type() == "stock" {
+        return;
+    }
+    if action == "pick-up" {
+        keyboard::activate(game_board, &stack, card.as_ref());
+    } else if let Some(card) = card {
+        // Cards aren't taken back off the foundations by accident
+        if stack.stack_type() != "foundation" && !smart_move(&card) {
+            game_board.error_bell();
+            // Translators: a card that has no legal move, like “Can't move 7 of Clubs”
+            let message = gettext("Can't move {card}").replace("{card}", &card.spoken_name());
+            game_board.announce(&message, gtk::AccessibleAnnouncementPriority::Medium);
+        }
+    }
+}
+
+/// Moves the card with the cards on top of it to a foundation if it can go
+/// there, otherwise onto a card on the tableau, then to an empty tableau stack
+/// and last to any other stack the game takes it on, like the waste in Tri-Peaks
+pub fn smart_move(card: &Card) -> bool {
+    let Some(origin) = card.stack() else {
+        return false;
+    };
+    if !games::verify_drag(card, &origin) {
+        return false;
+    }
+    let game_board = origin.game_board();
+    let mut destinations = Vec::new();
+    for stack in game_board.stacks() {
+        if stack == origin {
+            continue;
+        }
+        let preference = match (stack.stack_type().as_str(), stack.is_empty()) {
+            ("foundation", _) => 0,
+            ("tableau", false) => 1,
+            ("tableau", true) => 2,
+            _ => 3,
+        };
+        destinations.push((preference, stack));
+    }
+    destinations.sort_by_key(|(preference, _)| *preference);
+
+    let probe = TransferCardStack::probe(&origin, card);
+    let destination = destinations
+        .into_iter()
+        .map(|(_, stack)| stack)
+        .find(|stack| games::verify_drop(&probe, stack));
+    destination.is_some_and(|destination| keyboard::move_cards(card, &destination))
+}
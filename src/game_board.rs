@@ -18,7 +18,12 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use gtk::glib;
+use crate::{
+    card::Card, card_stack::CardStack, celebration::Celebration, clicks, keyboard, player::Player,
+    runtime, window::SolitaireWindow,
+};
+use adw::prelude::*;
+use gtk::{gdk, gio, glib, graphene};
 use gtk::{prelude::*, subclass::prelude::*};
 use std::cell::{Cell, RefCell};
 
@@ -38,6 +43,66 @@ glib::wrapper! {
         @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
 }
 
+// What is drawn under the stacks, chosen in the settings
+#[derive(Default)]
+pub enum Table {
+    #[default]
+    None,
+    Color(gdk::RGBA),
+    Felt(gdk::RGBA, gdk::Texture),
+    Image(gdk::Texture),
+}
+
+// A card on its way from where it was drawn before a move to its new place
+pub struct Flight {
+    card: Card,
+    from: graphene::Rect,
+    animation: adw::TimedAnimation,
+}
+
+impl Flight {
+    fn bounds(&self, game_board: &GameBoard) -> Option<graphene::Rect> {
+        let to = self.card.compute_bounds(game_board)?;
+        let t = self.animation.value() as f32;
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Some(graphene::Rect::new(
+            lerp(self.from.x(), to.x()),
+            lerp(self.from.y(), to.y()),
+            lerp(self.from.width(), to.width()),
+            lerp(self.from.height(), to.height()),
+        ))
+    }
+}
+
+// A see-through copy of a card gliding to where a hint suggests moving it
+pub struct Ghost {
+    card: Card,
+    from: graphene::Rect,
+    to: graphene::Rect,
+    animation: adw::TimedAnimation,
+}
+
+impl Ghost {
+    fn snapshot(&self, snapshot: &gtk::Snapshot) {
+        let Some(paintable) = self.card.paintable() else {
+            return;
+        };
+        let t = self.animation.value() as f32;
+        let x = self.from.x() + (self.to.x() - self.from.x()) * t;
+        let y = self.from.y() + (self.to.y() - self.from.y()) * t;
+        snapshot.push_opacity(0.6);
+        snapshot.save();
+        snapshot.translate(&graphene::Point::new(x, y));
+        paintable.snapshot(
+            snapshot,
+            self.from.width() as f64,
+            self.from.height() as f64,
+        );
+        snapshot.restore();
+        snapshot.pop();
+    }
+}
+
 mod imp {
     use super::*;
 
@@ -248,6 +313,15 @@ mod imp {
     #[derive(Default)]
     pub struct GameBoard {
         pub back: RefCell<Vec<gtk::Widget>>,
+        pub session: runtime::Session,
+        pub flights: RefCell<Vec<Flight>>,
+        pub ghost: RefCell<Option<Ghost>>,
+        pub input_blocks: Cell<u32>,
+        pub celebration: RefCell<Option<Celebration>>,
+        pub selection: RefCell<Vec<Card>>,
+        pub player: Player,
+        pub table: RefCell<Table>,
+        pub settings: std::cell::OnceCell<gio::Settings>,
     }
 
     #[glib::object_subclass]
@@ -261,7 +335,34 @@ mod imp {
         }
     }
 
-    impl ObjectImpl for GameBoard {}
+    impl ObjectImpl for GameBoard {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+            obj.connect_scale_factor_notify(|game_board| runtime::resize_cards(game_board));
+            keyboard::add_controller(&obj);
+            clicks::add_controller(&obj);
+
+            let settings = gio::Settings::new(crate::APP_ID);
+            settings.connect_changed(
+                None,
+                glib::clone!(
+                    #[weak]
+                    obj,
+                    move |_settings, key| match key {
+                        "card-back" | "card-back-image" | "four-color-suits" | "large-indices" => {
+                            runtime::reload_cards(&obj)
+                        }
+                        "table-background" | "table-color" | "table-image" => obj.update_table(),
+                        "no-moves-banner" => runtime::update_no_moves(&obj),
+                        _ => {}
+                    }
+                ),
+            );
+            self.settings.set(settings).unwrap();
+            obj.update_table();
+        }
+    }
 
     impl WidgetImpl for GameBoard {
         fn snapshot(&self, snapshot: &gtk::Snapshot) {
@@ -278,9 +379,37 @@ mod imp {
                 }
             }
             let obj = self.obj();
+            obj.snapshot_table(snapshot);
             for child in children.iter() {
                 obj.snapshot_child(child, snapshot);
             }
+
+            // Moving cards are hidden in their stacks and drawn above everything else
+            for flight in self.flights.borrow().iter() {
+                let Some(bounds) = flight.bounds(&obj) else {
+                    continue;
+                };
+                let Some(paintable) = flight.card.paintable() else {
+                    continue;
+                };
+                snapshot.save();
+                snapshot.translate(&graphene::Point::new(bounds.x(), bounds.y()));
+                paintable.snapshot(snapshot, bounds.width() as f64, bounds.height() as f64);
+                snapshot.restore();
+            }
+
+            if let Some(ghost) = self.ghost.borrow().as_ref() {
+                ghost.snapshot(snapshot);
+            }
+
+            if let Some(celebration) = self.celebration.borrow().as_ref() {
+                celebration.snapshot(snapshot);
+            }
+        }
+
+        fn size_allocate(&self, width: i32, height: i32, baseline: i32) {
+            self.parent_size_allocate(width, height, baseline);
+            runtime::resize_cards(&self.obj());
         }
 
         fn unrealize(&self) {
@@ -298,6 +427,42 @@ impl GameBoard {
         glib::Object::new()
     }
 
+    #[inline]
+    pub fn session(&self) -> &runtime::Session {
+        &self.imp().session
+    }
+
+    #[inline]
+    pub fn celebration(&self) -> &RefCell<Option<Celebration>> {
+        &self.imp().celebration
+    }
+
+    #[inline]
+    pub fn player(&self) -> &Player {
+        &self.imp().player
+    }
+
+    /// The cards picked up from the keyboard, starting with the one that was focused
+    #[inline]
+    pub fn selection(&self) -> &RefCell<Vec<Card>> {
+        &self.imp().selection
+    }
+
+    pub fn window(&self) -> Option<SolitaireWindow> {
+        self.root().and_downcast::<SolitaireWindow>()
+    }
+
+    /// The stacks of the current game, in the order they were added
+    pub fn stacks(&self) -> Vec<CardStack> {
+        let mut stacks = Vec::new();
+        let mut child = self.first_child();
+        while let Some(widget) = child {
+            child = widget.next_sibling();
+            stacks.extend(widget.downcast::<CardStack>().ok());
+        }
+        stacks
+    }
+
     pub fn add(
         &self,
         widget: &impl IsA<gtk::Widget>,
@@ -403,4 +568,274 @@ impl GameBoard {
     pub fn reset_positions(&self) {
         self.imp().back.borrow_mut().clear();
     }
+
+    /// Reads the table background from the settings
+    pub fn update_table(&self) {
+        let settings = gio::Settings::new(crate::APP_ID);
+        let color = gdk::RGBA::parse(settings.string("table-color").as_str())
+            .unwrap_or(gdk::RGBA::new(0.16, 0.42, 0.25, 1.0));
+        let table = match settings.string("table-background").as_str() {
+            "color" => Table::Color(color),
+            "felt" => Table::Felt(color, felt_texture()),
+            "image" => {
+                let uri = settings.string("table-image");
+                match gdk::Texture::from_file(&gio::File::for_uri(&uri)) {
+                    Ok(texture) => Table::Image(texture),
+                    Err(error) => {
+                        glib::g_warning!("solitaire", "Couldn't load the table image: {error}");
+                        Table::None
+                    }
+                }
+            }
+            _ => Table::None,
+        };
+        self.imp().table.replace(table);
+        self.queue_draw();
+    }
+
+    fn snapshot_table(&self, snapshot: &gtk::Snapshot) {
+        let bounds = graphene::Rect::new(0.0, 0.0, self.width() as f32, self.height() as f32);
+        match &*self.imp().table.borrow() {
+            Table::None => {}
+            Table::Color(color) => snapshot.append_color(color, &bounds),
+            Table::Felt(color, texture) => {
+                snapshot.append_color(color, &bounds);
+                let tile =
+                    graphene::Rect::new(0.0, 0.0, texture.width() as f32, texture.height() as f32);
+                snapshot.push_repeat(&bounds, Some(&tile));
+                snapshot.append_texture(texture, &tile);
+                snapshot.pop();
+            }
+            Table::Image(texture) => {
+                // Cover the whole board, cropping what doesn't fit
+                let (width, height) = (texture.width() as f32, texture.height() as f32);
+                let scale = (bounds.width() / width).max(bounds.height() / height);
+                let image = graphene::Rect::new(
+                    (bounds.width() - width * scale) / 2.0,
+                    (bounds.height() - height * scale) / 2.0,
+                    width * scale,
+                    height * scale,
+                );
+                snapshot.push_clip(&bounds);
+                snapshot.append_texture(texture, &image);
+                snapshot.pop();
+            }
+        }
+    }
+
+    /// How wide the cards are shown, zero before the first game is laid out
+    pub fn card_width(&self) -> i32 {
+        let mut stack = self.first_child();
+        while let Some(widget) = stack {
+            let mut card = widget.first_child();
+            while let Some(child) = card {
+                if child.is::<Card>() && child.width() > 0 {
+                    return child.width();
+                }
+                card = child.next_sibling();
+            }
+            stack = widget.next_sibling();
+        }
+        0
+    }
+
+    /// Stops cards from being picked up or dropped until `unblock_input` is called
+    pub fn block_input(&self) {
+        let imp = self.imp();
+        imp.input_blocks.set(imp.input_blocks.get() + 1);
+        self.set_can_target(false);
+    }
+
+    pub fn unblock_input(&self) {
+        let imp = self.imp();
+        let blocks = imp.input_blocks.get().saturating_sub(1);
+        imp.input_blocks.set(blocks);
+        self.set_can_target(blocks == 0);
+    }
+
+    /// How long a card takes to move to its new place, in milliseconds
+    pub fn animation_duration(&self) -> u32 {
+        let settings = gio::Settings::new(crate::APP_ID);
+        if settings.boolean("reduced-motion") || !self.settings().is_gtk_enable_animations() {
+            0
+        } else {
+            settings.uint("animation-duration")
+        }
+    }
+
+    /// Where the given cards are drawn right now, including cards that are still moving
+    pub fn card_bounds(&self, cards: &[Card]) -> Vec<(Card, graphene::Rect)> {
+        let flights = self.imp().flights.borrow();
+        cards
+            .iter()
+            .filter_map(|card| {
+                let bounds = match flights.iter().find(|flight| &flight.card == card) {
+                    Some(flight) => flight.bounds(self),
+                    None => card.compute_bounds(self),
+                }?;
+                Some((card.clone(), bounds))
+            })
+            .collect()
+    }
+
+    /// Moves cards that were just put in another stack smoothly from where
+    /// `card_bounds` found them to their new place
+    pub fn animate_cards(&self, starts: Vec<(Card, graphene::Rect)>) {
+        let duration = self.animation_duration();
+        if starts.is_empty() || duration == 0 {
+            return;
+        }
+        let target = adw::CallbackAnimationTarget::new(glib::clone!(
+            #[weak(rename_to=game_board)]
+            self,
+            move |_value| game_board.queue_draw()
+        ));
+        let animation = adw::TimedAnimation::new(self, 0.0, 1.0, duration, target);
+        animation.set_easing(adw::Easing::EaseOutCubic);
+        {
+            let mut flights = self.imp().flights.borrow_mut();
+            // Cards that were already moving start over from where they are now
+            flights.retain(|flight| !starts.iter().any(|(card, _)| card == &flight.card));
+            for (card, from) in starts {
+                card.set_opacity(0.0);
+                flights.push(Flight {
+                    card,
+                    from,
+                    animation: animation.clone(),
+                });
+            }
+        }
+        self.block_input();
+        animation.connect_done(glib::clone!(
+            #[weak(rename_to=game_board)]
+            self,
+            move |animation| game_board.land(animation)
+        ));
+        animation.play();
+    }
+
+    fn land(&self, animation: &adw::TimedAnimation) {
+        self.imp().flights.borrow_mut().retain(|flight| {
+            if &flight.animation == animation {
+                flight.card.set_opacity(1.0);
+                false
+            } else {
+                true
+            }
+        });
+        self.unblock_input();
+        self.queue_draw();
+    }
+
+    /// Points out a move: the cards to move are darkened, the stack they go to
+    /// is outlined and a see-through copy of the card glides there
+    pub fn show_hint(&self, move_: &runtime::Move) {
+        self.clear_hint();
+        let (Some(origin), Some(destination)) = (
+            runtime::get_stack(self, &move_.origin_stack),
+            runtime::get_stack(self, &move_.destination_stack),
+        ) else {
+            return;
+        };
+        let Some(card) = runtime::get_child(&origin, &move_.card_name)
+            .ok()
+            .and_downcast::<Card>()
+        else {
+            return;
+        };
+        origin.hint_card(move_.card_name.clone());
+        destination.add_css_class("hint-destination");
+
+        let duration = self.animation_duration();
+        let (Some(from), Some(to)) = (card.compute_bounds(self), destination.drop_bounds(self))
+        else {
+            return;
+        };
+        if duration == 0 {
+            return;
+        }
+        let target = adw::CallbackAnimationTarget::new(glib::clone!(
+            #[weak(rename_to=game_board)]
+            self,
+            move |_value| game_board.queue_draw()
+        ));
+        // Slower than a move, so it can be followed
+        let animation = adw::TimedAnimation::new(self, 0.0, 1.0, duration * 4, target);
+        animation.set_easing(adw::Easing::EaseInOutCubic);
+        animation.connect_done(glib::clone!(
+            #[weak(rename_to=game_board)]
+            self,
+            move |animation| {
+                let mut ghost = game_board.imp().ghost.borrow_mut();
+                if ghost
+                    .as_ref()
+                    .is_some_and(|ghost| &ghost.animation == animation)
+                {
+                    *ghost = None;
+                }
+                game_board.queue_draw();
+            }
+        ));
+        self.imp().ghost.replace(Some(Ghost {
+            card,
+            from,
+            to,
+            animation: animation.clone(),
+        }));
+        animation.play();
+    }
+
+    /// Takes down the hint shown by `show_hint`
+    pub fn clear_hint(&self) {
+        for stack in self.stacks() {
+            stack.remove_css_class("hint-destination");
+            for i in 1..=stack.n_cards() {
+                if let Some(card) = stack.get_card(i) {
+                    card.remove_css_class("highlight");
+                }
+            }
+        }
+        if let Some(ghost) = self.imp().ghost.take() {
+            ghost.animation.reset();
+            self.queue_draw();
+        }
+    }
+
+    /// Puts every moving card in its place right away
+    pub fn finish_animations(&self) {
+        let animations: Vec<adw::TimedAnimation> = self
+            .imp()
+            .flights
+            .borrow()
+            .iter()
+            .map(|flight| flight.animation.clone())
+            .collect();
+        for animation in animations {
+            animation.skip();
+        }
+    }
+}
+
+// A tile of light and dark specks that makes a plain color look like felt
+fn felt_texture() -> gdk::Texture {
+    const SIZE: i32 = 128;
+    let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for _ in 0..SIZE * SIZE {
+        let alpha = glib::random_int_range(0, 24) as u8;
+        // Premultiplied, so white specks have every channel at the alpha
+        let value = if glib::random_int_range(0, 2) == 0 {
+            alpha
+        } else {
+            0
+        };
+        data.extend_from_slice(&[value, value, value, alpha]);
+    }
+    gdk::MemoryTexture::new(
+        SIZE,
+        SIZE,
+        gdk::MemoryFormat::B8g8r8a8Premultiplied,
+        &glib::Bytes::from_owned(data),
+        (SIZE * 4) as usize,
+    )
+    .upcast()
 }
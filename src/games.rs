@@ -27,7 +27,8 @@ use crate::{
 use adw::prelude::*;
 use gettextrs::gettext;
 use gtk::{gio, glib};
-use std::sync::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::AtomicBool;
 
 mod freecell;
 mod klondike;
@@ -41,10 +42,233 @@ pub const RANKS: [&str; 13] = [
     "ace", "2", "3", "4", "5", "6", "7", "8", "9", "10", "jack", "queen", "king",
 ];
 
-static CURRENT_GAME: Mutex<Option<Box<dyn Game>>> = Mutex::new(None);
+/// The family a game belongs to, used to group games in the chooser.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameCategory {
+    Klondike,
+    FreeCell,
+    Golf,
+    #[cfg(debug_assertions)]
+    Debug,
+}
+
+impl GameCategory {
+    pub fn name(self) -> String {
+        match self {
+            Self::Klondike => gettext("Klondike Games"),
+            Self::FreeCell => gettext("FreeCell Games"),
+            Self::Golf => gettext("Golf Games"),
+            #[cfg(debug_assertions)]
+            Self::Debug => gettext("Debug"),
+        }
+    }
+}
+
+/// A rule variant the player can toggle for a game, such as drawing three cards.
+pub struct RuleOption {
+    pub id: &'static str,
+    pub title: fn() -> String,
+    pub default: bool,
+}
+
+/// The rule variants chosen for a single game, keyed by `RuleOption::id`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameOptions(BTreeMap<String, bool>);
+
+impl GameOptions {
+    pub fn get(&self, id: &str) -> bool {
+        self.0.get(id).copied().unwrap_or(false)
+    }
+
+    pub fn set(&mut self, id: &str, value: bool) {
+        self.0.insert(id.to_string(), value);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.0.iter().map(|(id, value)| (id.as_str(), *value))
+    }
+}
+
+pub struct GameDescriptor {
+    /// Stable identifier, used in settings and never translated
+    pub id: &'static str,
+    pub name: fn() -> String,
+    pub description: fn() -> String,
+    pub category: GameCategory,
+    pub options: &'static [RuleOption],
+    /// Whether moves win or lose points
+    pub scored: bool,
+    new_game: fn(Vec<Card>, &GameBoard, &GameOptions, &mut Rand) -> Box<dyn Game>,
+}
+
+impl GameDescriptor {
+    /// Reads the chosen rule options from settings, falling back to their defaults.
+    pub fn load_options(&self) -> GameOptions {
+        let settings = gio::Settings::new(crate::APP_ID);
+        let saved = settings.get::<HashMap<String, bool>>("game-options");
+        let mut options = GameOptions::default();
+        for option in self.options {
+            let key = format!("{}/{}", self.id, option.id);
+            options.set(
+                option.id,
+                saved.get(&key).copied().unwrap_or(option.default),
+            );
+        }
+        options
+    }
+
+    pub fn save_option(&self, option_id: &str, value: bool) {
+        let settings = gio::Settings::new(crate::APP_ID);
+        let mut saved = settings.get::<HashMap<String, bool>>("game-options");
+        saved.insert(format!("{}/{}", self.id, option_id), value);
+        settings
+            .set("game-options", saved)
+            .expect("Failed to save game options");
+    }
+}
+
+fn construct<G: Game + 'static>(
+    cards: Vec<Card>,
+    game_board: &GameBoard,
+    options: &GameOptions,
+    rand: &mut Rand,
+) -> Box<dyn Game> {
+    Box::new(G::new_game(cards, game_board, options, rand))
+}
+
+pub static GAMES: &[GameDescriptor] = &[
+    #[cfg(debug_assertions)]
+    GameDescriptor {
+        id: "test",
+        name: || gettext("Test"),
+        description: || gettext("Test Game"),
+        category: GameCategory::Debug,
+        options: &[],
+        scored: false,
+        new_game: construct::<test::Test>,
+    },
+    GameDescriptor {
+        id: "klondike",
+        name: || gettext("Klondike"),
+        description: || gettext("Classic Solitaire"),
+        category: GameCategory::Klondike,
+        options: &[],
+        scored: true,
+        new_game: construct::<klondike::Klondike>,
+    },
+    GameDescriptor {
+        id: "freecell",
+        name: || gettext("FreeCell"),
+        description: || gettext("Build Foundations using Free Cells"),
+        category: GameCategory::FreeCell,
+        options: &[],
+        scored: false,
+        new_game: construct::<freecell::FreeCell>,
+    },
+    GameDescriptor {
+        id: "tri-peaks",
+        name: || gettext("Tri-Peaks"),
+        description: || gettext("Clear Three Peaks of Cards"),
+        category: GameCategory::Golf,
+        options: &[],
+        scored: false,
+        new_game: construct::<tri_peaks::TriPeaks>,
+    },
+]; //, "Spider", "Pyramid", "Yukon"] not yet :)
+
+/// How to deal a new game
+#[derive(Clone, Copy, Debug)]
+pub struct Deal {
+    /// The same seed always deals the same cards
+    pub seed: Option<u32>,
+    /// Whether to deal again until the solver finds a way to win
+    pub solver_check: bool,
+}
+
+/// Random numbers for dealing, kept apart from GLib's global ones so that
+/// nothing else drawing from those changes a seeded deal
+pub struct Rand {
+    state: u64,
+}
+
+impl Rand {
+    pub fn with_seed(seed: u32) -> Self {
+        Self {
+            state: u64::from(seed),
+        }
+    }
+
+    /// A number from `begin` up to but not including `end`, like `g_rand_int_range`
+    pub fn int_range(&mut self, begin: i32, end: i32) -> i32 {
+        // SplitMix64
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        let range = i64::from(end) - i64::from(begin);
+        if range <= 0 {
+            return begin;
+        }
+        (i64::from(begin) + (z % range as u64) as i64) as i32
+    }
+}
+
+impl Default for Deal {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            solver_check: true,
+        }
+    }
+}
+
+pub fn get_game(game_id: &str) -> Option<&'static GameDescriptor> {
+    GAMES.iter().find(|game| game.id == game_id)
+}
+
+pub fn load_game(game_id: &str, game_board: &GameBoard, seed: Option<u32>) {
+    let Some(descriptor) = get_game(game_id) else {
+        glib::g_critical!("solitaire", "Unknown game: {game_id}");
+        return;
+    };
+    let seed = seed.unwrap_or_else(glib::random_int);
+    load_game_with_options(descriptor, &descriptor.load_options(), seed, game_board);
+}
+
+/// Older versions saved the recent game by its display name, translated or
+/// not. Turns such a name into the game's id, or forgets it if none matches.
+pub fn migrate_recent_game() {
+    let settings = gio::Settings::new(crate::APP_ID);
+    let recent_game = settings.string("recent-game");
+    if recent_game == "none" || get_game(&recent_game).is_some() {
+        return;
+    }
+    let game = GAMES.iter().find(|game| {
+        game.id.eq_ignore_ascii_case(&recent_game) || (game.name)() == recent_game.as_str()
+    });
+    glib::g_message!(
+        "solitaire",
+        "Migrating the recent game {recent_game} to {}",
+        game.map_or("none", |game| game.id)
+    );
+    match game {
+        Some(game) => settings
+            .set_string("recent-game", game.id)
+            .expect("Failed to save the recent game"),
+        None => settings.reset("recent-game"),
+    }
+}
 
-pub fn load_game(game_name: &str, game_board: &GameBoard) {
-    let window = crate::window::SolitaireWindow::get_window().unwrap();
+/// Deals a game with the given rules rather than the ones chosen in settings
+pub fn load_game_with_options(
+    descriptor: &'static GameDescriptor,
+    options: &GameOptions,
+    seed: u32,
+    game_board: &GameBoard,
+) {
+    glib::g_message!("solitaire", "Dealing {} with seed {seed}", descriptor.id);
+    let window = game_board.window().unwrap();
     window
         .lookup_action("undo")
         .unwrap()
@@ -59,37 +283,42 @@ pub fn load_game(game_name: &str, game_board: &GameBoard) {
         .set_enabled(false);
     window.set_hint_drop_enabled(false);
 
-    let mut cards = runtime::get_cards();
-    let theme_name = renderer::get_requested_theme();
-    if theme_name != renderer::ACTIVE_THEME.with_borrow(|t| t.clone()) {
-        cards.clear();
-    }
-
+    let mut cards = runtime::get_cards(game_board);
     if cards.is_empty() {
-        let card_theme = renderer::get_card_theme(&theme_name);
-        renderer::create_cards(&card_theme, &mut cards);
-        renderer::ACTIVE_THEME.set(theme_name);
+        renderer::create_cards(&mut cards);
+        runtime::set_cards(game_board, cards.clone());
     }
-
-    // Store the current game type
-    let mut game = CURRENT_GAME.lock().unwrap();
-    match game_name {
-        #[cfg(debug_assertions)]
-        "Test" => *game = Some(Box::new(test::Test::new_game(cards, &game_board))),
-        "Klondike" => *game = Some(Box::new(klondike::Klondike::new_game(cards, &game_board))),
-        "FreeCell" => *game = Some(Box::new(freecell::FreeCell::new_game(cards, &game_board))),
-        "Tri-Peaks" => *game = Some(Box::new(tri_peaks::TriPeaks::new_game(cards, &game_board))),
-        _ => panic!("Unknown game: {}", game_name),
+    // The last game leaves the cards in any order, so start the deal from a fixed one
+    cards.sort_by_key(Card::id);
+    // The cards keep their current images until the new theme is ready
+    let theme_name = renderer::get_requested_theme();
+    if theme_name != runtime::get_theme(game_board) {
+        runtime::load_theme(game_board, theme_name);
     }
+
+    // Store the current game type. The same seed always deals the same cards.
+    let mut rand = Rand::with_seed(seed);
+    let game = (descriptor.new_game)(cards, game_board, options, &mut rand);
+    let session = game_board.session();
+    session.game.replace(Some(game));
+    session.game_id.set(descriptor.id);
+    session.options.replace(options.clone());
+    session.seed.set(seed);
+    window.update_score();
+    runtime::save_start_position(game_board);
+    runtime::update_no_moves(game_board);
 }
 
 pub fn unload(game_board: &GameBoard) {
-    let mut game = CURRENT_GAME.lock().unwrap();
-    *game = None;
+    game_board.session().game.replace(None);
+    runtime::update_no_moves(game_board);
+    crate::celebration::cancel(game_board);
+    crate::player::close(game_board);
+    crate::keyboard::clear_selection(game_board);
+    runtime::clear_hints(game_board);
+    game_board.finish_animations();
     game_board.reset_positions();
-    runtime::clear_history_and_moves();
-    runtime::clear_state();
-    runtime::update_deals(0);
+    runtime::clear_history_and_moves(game_board);
     let items = game_board.observe_children().n_items();
     let mut cards = Vec::new();
     for _ in 0..items {
@@ -99,167 +328,214 @@ pub fn unload(game_board: &GameBoard) {
             .unwrap()
             .destroy_and_return_cards(&mut cards);
     }
-    runtime::set_cards(cards);
+    runtime::set_cards(game_board, cards);
     game_board.reset_layout();
 }
 
-pub fn get_games() -> Vec<String> {
-    vec![
-        #[cfg(debug_assertions)]
-        gettext("Test"),
-        gettext("Klondike"),
-        gettext("FreeCell"),
-        gettext("Tri-Peaks"),
-    ] //, "Spider", "Pyramid", "Yukon"] not yet :)
-}
-
-pub fn get_game_description(game_name: &str) -> String {
-    match game_name {
-        #[cfg(debug_assertions)]
-        "Test" => gettext("Test Game"),
-        "Klondike" => gettext("Classic Solitaire"),
-        "FreeCell" => gettext("Build Foundations using Free Cells"),
-        "Tri-Peaks" => gettext("Clear Three Peaks of Cards"),
-        _ => "".to_string(),
-    }
+fn with_game<R>(game_board: &GameBoard, f: impl FnOnce(&mut dyn Game) -> R) -> Option<R> {
+    let mut game = game_board.session().game.borrow_mut();
+    game.as_mut().map(|game| f(game.as_mut()))
 }
 
 pub fn on_double_click(card: &Card) {
-    let mut game = CURRENT_GAME.lock().unwrap();
-    if let Some(game) = game.as_mut() {
-        game.card_double_click(card);
+    if let Some(card_stack) = card.stack() {
+        let game_board = card_stack.game_board();
+        runtime::start_transaction(&game_board);
+        with_game(&game_board, |game| game.card_double_click(card));
+        runtime::end_transaction(&game_board);
     }
 }
 
 pub fn stack_click(stack: &CardStack) {
-    let mut game = CURRENT_GAME.lock().unwrap();
-    if let Some(game) = game.as_mut() {
-        game.stack_click(stack);
+    let game_board = stack.game_board();
+    runtime::start_transaction(&game_board);
+    with_game(&game_board, |game| game.stack_click(stack));
+    runtime::end_transaction(&game_board);
+}
+
+pub fn can_click(stack: &CardStack) -> bool {
+    with_game(&stack.game_board(), |game| game.can_click(stack)).unwrap_or(false)
+}
+
+/// Whether the rules allow any move at all, trying every face-up card on
+/// every stack. Unlike `candidate_moves`, this doesn't depend on what the
+/// solver chooses to look at.
+pub fn has_legal_move(game_board: &GameBoard) -> bool {
+    let stacks = game_board.stacks();
+    if stacks.iter().any(can_click) {
+        return true;
     }
+    stacks.iter().any(|origin| {
+        (1..=origin.n_cards())
+            .filter_map(|i| origin.get_card(i))
+            .filter(|card| card.is_face_up() && verify_drag(card, origin))
+            .any(|card| {
+                let probe = TransferCardStack::probe(origin, &card);
+                stacks
+                    .iter()
+                    .any(|stack| stack != origin && verify_drop(&probe, stack))
+            })
+    })
 }
 
 pub fn drag_completed(
     origin_stack: &CardStack,
     destination_stack: &CardStack,
-    move_: &mut runtime::Move,
+    move_: &runtime::Move,
 ) {
-    let mut game = CURRENT_GAME.lock().unwrap();
-    if let Some(game) = game.as_mut() {
-        game.drag_completed(origin_stack, destination_stack, move_);
-    }
+    with_game(&origin_stack.game_board(), |game| {
+        game.drag_completed(origin_stack, destination_stack, move_)
+    });
+}
+
+pub fn pre_undo_drag(origin_stack: &CardStack, dropped_stack: &CardStack, move_: &runtime::Move) {
+    with_game(&origin_stack.game_board(), |game| {
+        game.pre_undo_drag(origin_stack, dropped_stack, move_)
+    });
 }
 
-pub fn pre_undo_drag(
+pub fn post_redo_drag(
     origin_stack: &CardStack,
-    dropped_stack: &CardStack,
-    move_: &mut runtime::Move,
+    destination_stack: &CardStack,
+    move_: &runtime::Move,
 ) {
-    let mut game = CURRENT_GAME.lock().unwrap();
-    if let Some(game) = game.as_mut() {
-        game.pre_undo_drag(origin_stack, dropped_stack, move_);
-    }
+    with_game(&origin_stack.game_board(), |game| {
+        game.post_redo_drag(origin_stack, destination_stack, move_)
+    });
 }
 
 pub fn verify_drag(bottom_card: &Card, from_stack: &CardStack) -> bool {
-    let mut game = CURRENT_GAME.lock().unwrap();
-    if let Some(game) = game.as_mut() {
+    with_game(&from_stack.game_board(), |game| {
         game.verify_drag(bottom_card, from_stack)
-    } else {
-        false
-    }
+    })
+    .unwrap_or(false)
 }
 
 pub fn verify_drop(transfer_stack: &TransferCardStack, to_stack: &CardStack) -> bool {
-    let mut game = CURRENT_GAME.lock().unwrap();
-    if let Some(game) = game.as_mut() {
+    with_game(&to_stack.game_board(), |game| {
         game.verify_drop(transfer_stack, to_stack)
-    } else {
-        false
-    }
+    })
+    .unwrap_or(false)
+}
+
+pub fn is_won_fn(game_board: &GameBoard) -> Option<runtime::WonFn> {
+    with_game(game_board, |game| game.is_won_fn())
 }
 
-pub fn is_won_fn() -> Box<dyn FnMut(&mut solver::State) -> bool> {
-    let mut game = CURRENT_GAME.lock().unwrap();
-    game.as_mut().unwrap().is_won_fn()
+/// Gets the move generator and win check of the session's game, to be moved onto a solver thread
+pub fn solver_fns(game_board: &GameBoard) -> Option<(solver::MoveGenerator, runtime::WonFn)> {
+    with_game(game_board, |game| (game.move_generator(), game.is_won_fn()))
 }
 
+/// The next move autoplay would make, if any
+pub fn autoplay_move(game_board: &GameBoard) -> Option<runtime::Move> {
+    foundation_move(game_board, false)
+}
+
+/// The next move towards the end of a game that can't be lost anymore
+pub fn finishing_move(game_board: &GameBoard) -> Option<runtime::Move> {
+    foundation_move(game_board, true)
+}
+
+fn foundation_move(game_board: &GameBoard, finishing: bool) -> Option<runtime::Move> {
+    let (stack_names, game_state) = runtime::get_solver_state(game_board);
+    let mut state = solver::new_ghost_state(game_state);
+    let (move_option, _safe) = with_game(game_board, |game| {
+        if finishing && !game.is_trivially_won(&mut state) {
+            return None;
+        }
+        // Once the game is won any move home will do
+        game.foundation_moves(&mut state)
+            .into_iter()
+            .find(|(_move_option, safe)| *safe || finishing)
+    })??;
+    Some(to_runtime_move(&stack_names, &move_option))
+}
+
+/// The moves that can be made in the current position, the most promising first
+pub fn candidate_moves(game_board: &GameBoard) -> Vec<runtime::Move> {
+    let Some((mut move_generator, _is_won_fn)) = solver_fns(game_board) else {
+        return Vec::new();
+    };
+    let (stack_names, game_state) = runtime::get_solver_state(game_board);
+    solver::candidate_moves(game_state, &mut move_generator)
+        .iter()
+        .map(|move_option| to_runtime_move(&stack_names, move_option))
+        .collect()
+}
+
+fn to_runtime_move(stack_names: &[String], move_option: &solver::Move) -> runtime::Move {
+    runtime::Move {
+        origin_stack: stack_names[move_option.origin_stack].clone(),
+        card_name: solver::solver_card_to_name(move_option.card).to_string(),
+        destination_stack: stack_names[move_option.destination_stack].clone(),
+        instruction: move_option.instruction.clone(),
+    }
+}
+
+pub mod notation;
 pub mod solver;
 
-pub async fn try_game(game_name: &str, game_board: &GameBoard) -> Option<Vec<runtime::Move>> {
-    solver::set_should_stop(false);
-    for _ in 0..3 {
-        if solver::get_should_stop() {
+pub async fn try_game(
+    game_id: &str,
+    game_board: &GameBoard,
+    deal: Deal,
+) -> Option<Vec<runtime::Move>> {
+    let session = game_board.session();
+    session.set_should_stop(false);
+    // Dealing again with the same seed would give the same cards
+    let attempts = if deal.seed.is_some() { 1 } else { 3 };
+    for _ in 0..attempts {
+        if session.get_should_stop() {
             return None;
         }
-        load_game(game_name, &game_board);
-        let mut stack_names = Vec::new();
-        let stacks = game_board.observe_children();
-        let mut game_state = Vec::new();
-        for i in 0..stacks.n_items() {
-            let stack = stacks.item(i).unwrap().downcast::<CardStack>().unwrap();
-            game_state.push(stack.get_solver_stack());
-            stack_names.push(stack.widget_name().to_string());
+        load_game(game_id, game_board, deal.seed);
+        if !deal.solver_check {
+            return Some(Vec::new());
         }
+        let (stack_names, game_state) = runtime::get_solver_state(game_board);
         #[cfg(feature = "solver-debug")]
-        solver::solver_debug(
-            &crate::window::SolitaireWindow::get_window().unwrap(),
-            game_state.clone(),
-            stack_names.clone(),
-        );
+        solver::solver_debug(game_board, game_state.clone(), stack_names.clone());
 
+        let (move_generator, is_won_fn) = solver_fns(game_board)?;
+        let should_stop = session.stop_flag();
         let (sender, receiver) = async_channel::bounded(1);
         std::thread::spawn(move || {
-            let mut game = CURRENT_GAME.lock().unwrap();
-            if let Some(game) = game.as_mut() {
-                let result = solver::solve(game_state, game.move_generator(), game.is_won_fn());
-                sender.send_blocking(result).unwrap();
-            }
+            let result = solver::solve(game_state, move_generator, is_won_fn, &should_stop);
+            sender.send_blocking(result).unwrap();
         });
         while let Ok(result) = receiver.recv().await {
             if let Some(solver_history) = result {
-                let mut history = Vec::new();
-                for move_option in &solver_history {
-                    history.push(runtime::Move {
-                        origin_stack: stack_names[move_option.origin_stack].clone(),
-                        card_name: solver::solver_card_to_name(move_option.card).to_string(),
-                        destination_stack: stack_names[move_option.destination_stack].clone(),
-                        instruction: move_option.instruction.clone(),
-                        flip_index: move_option.flip_index,
-                    });
-                }
+                let history: Vec<runtime::Move> = solver_history
+                    .iter()
+                    .map(|move_option| to_runtime_move(&stack_names, move_option))
+                    .collect();
                 for move_option in &history {
                     println!("{:?}", move_option);
                 }
                 return Some(history);
             }
         }
-        unload(&game_board);
+        unload(game_board);
     }
 
     // Couldn't find a solution
     None
 }
 
-pub fn re_solve(stack_names: Vec<String>, game_state: Vec<Vec<u8>>) -> Option<Vec<runtime::Move>> {
-    let (move_generator, is_won_fn);
-    {
-        let mut game = CURRENT_GAME.lock().unwrap();
-        let game = game.as_mut().unwrap();
-        move_generator = game.move_generator();
-        is_won_fn = game.is_won_fn();
-    }
-    solver::set_should_stop(false);
-    let result = solver::solve(game_state, move_generator, is_won_fn);
-    let mut history = Vec::new();
-    for move_option in result? {
-        history.push(runtime::Move {
-            origin_stack: stack_names[move_option.origin_stack].clone(),
-            card_name: solver::solver_card_to_name(move_option.card).to_string(),
-            destination_stack: stack_names[move_option.destination_stack].clone(),
-            instruction: move_option.instruction.clone(),
-            flip_index: move_option.flip_index,
-        });
-    }
+pub fn re_solve(
+    stack_names: Vec<String>,
+    game_state: Vec<Vec<u8>>,
+    move_generator: solver::MoveGenerator,
+    is_won_fn: runtime::WonFn,
+    should_stop: &AtomicBool,
+) -> Option<Vec<runtime::Move>> {
+    should_stop.store(false, std::sync::atomic::Ordering::SeqCst);
+    let result = solver::solve(game_state, move_generator, is_won_fn, should_stop);
+    let history = result?
+        .iter()
+        .map(|move_option| to_runtime_move(&stack_names, move_option))
+        .collect();
     Some(history)
 }
 
@@ -323,26 +599,79 @@ pub fn test_solver_state() {
     );
 }
 
-trait Game: Send + Sync {
-    fn new_game(cards: Vec<Card>, game_board: &GameBoard) -> Self
+/// Deals solver cards the way the games draw them, starting once from the
+/// usual order and once from the reverse, and checks the seed alone decides
+/// the deal
+pub fn test_seeded_deal() {
+    const SEED: u32 = 11982;
+    let deal = |mut cards: Vec<u8>, seed: u32| {
+        // Like `load_game_with_options`, which sorts the cards by id
+        cards.sort_unstable();
+        let mut rand = Rand::with_seed(seed);
+        let mut dealt = Vec::new();
+        while !cards.is_empty() {
+            let random_card = rand.int_range(0, cards.len() as i32) as usize;
+            dealt.push(cards.remove(random_card));
+        }
+        dealt
+    };
+    let cards = (0..52).collect::<Vec<u8>>();
+    let first = deal(cards.clone(), SEED);
+    assert_eq!(
+        first,
+        deal(cards.iter().rev().copied().collect(), SEED),
+        "seed {SEED} dealt differently"
+    );
+    assert_ne!(
+        first,
+        deal(cards, SEED + 1),
+        "seeds {SEED} and {} dealt the same",
+        SEED + 1
+    );
+}
+
+pub trait Game {
+    fn new_game(
+        cards: Vec<Card>,
+        game_board: &GameBoard,
+        options: &GameOptions,
+        rand: &mut Rand,
+    ) -> Self
     where
         Self: Sized;
     fn verify_drag(&self, bottom_card: &Card, from_stack: &CardStack) -> bool;
     fn verify_drop(&self, transfer_stack: &TransferCardStack, to_stack: &CardStack) -> bool;
+    // Anything this does to the board must be recorded with `runtime`,
+    // so it becomes part of the same undo step as the move
     fn drag_completed(
-        &self,
+        &mut self,
         origin_stack: &CardStack,
         destination_stack: &CardStack,
-        move_: &mut runtime::Move,
+        move_: &runtime::Move,
     );
+    // Undo and redo restore the board from the history, these two only
+    // keep the game's own state in sync
     fn pre_undo_drag(
-        &self,
+        &mut self,
         previous_origin_stack: &CardStack,
         previous_destination_stack: &CardStack,
-        move_: &mut runtime::Move,
+        move_: &runtime::Move,
+    );
+    fn post_redo_drag(
+        &mut self,
+        origin_stack: &CardStack,
+        destination_stack: &CardStack,
+        move_: &runtime::Move,
     );
-    fn card_double_click(&self, card: &Card);
-    fn stack_click(&self, slot: &CardStack);
-    fn move_generator(&self) -> Box<dyn FnMut(&mut solver::State)>;
-    fn is_won_fn(&self) -> Box<dyn FnMut(&mut solver::State) -> bool>;
+    fn card_double_click(&mut self, card: &Card);
+    fn stack_click(&mut self, slot: &CardStack);
+    // Whether `stack_click` would do anything with the stack
+    fn can_click(&self, slot: &CardStack) -> bool;
+    fn move_generator(&self) -> solver::MoveGenerator;
+    fn is_won_fn(&self) -> runtime::WonFn;
+    // Moves to the foundations, flagged when autoplay may make them on the
+    // player's behalf by the solver's safety rule
+    fn foundation_moves(&self, state: &mut solver::State) -> Vec<(solver::Move, bool)>;
+    // Whether the rest of the game is mechanical, finishing it only takes foundation moves
+    fn is_trivially_won(&self, state: &mut solver::State) -> bool;
 }
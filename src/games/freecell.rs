@@ -29,10 +29,19 @@ pub struct FreeCell {}
 
 impl FreeCell {}
 
+const CELLS: &[usize] = &[0, 1, 2, 3];
 const FOUNDATION: &[usize] = &[4, 5, 6, 7];
+const TABLEAU: &[usize] = &[8, 9, 10, 11, 12, 13, 14, 15];
+// The cells and the tableau, the stacks cards can be played home from
+const TO_FOUNDATION: &[usize] = &[0, 1, 2, 3, 8, 9, 10, 11, 12, 13, 14, 15];
 
 impl Game for FreeCell {
-    fn new_game(mut cards: Vec<Card>, game_board: &GameBoard) -> Self {
+    fn new_game(
+        mut cards: Vec<Card>,
+        game_board: &GameBoard,
+        _options: &GameOptions,
+        rand: &mut Rand,
+    ) -> Self {
         let mut n_cards = cards.len() as i32;
 
         for i in 0..4 {
@@ -52,7 +61,7 @@ impl Game for FreeCell {
             let cards_needed = if i < 4 { 7 } else { 6 };
 
             for _ in 0..cards_needed {
-                let random_card = glib::random_int_range(0, n_cards) as usize;
+                let random_card = rand.int_range(0, n_cards) as usize;
                 if let Some(card) = cards.get(random_card) {
                     card_stack.add_card(&card);
                     card_stack.add_drag_to_card(&card);
@@ -125,22 +134,30 @@ impl Game for FreeCell {
     }
 
     fn drag_completed(
-        &self,
+        &mut self,
         _origin_stack: &CardStack,
         _destination_stack: &CardStack,
-        _move: &mut runtime::Move,
+        _move: &runtime::Move,
     ) {
     }
 
     fn pre_undo_drag(
-        &self,
+        &mut self,
         _origin_stack: &CardStack,
         _dropped_stack: &CardStack,
-        _move: &mut runtime::Move,
+        _move: &runtime::Move,
     ) {
     }
 
-    fn card_double_click(&self, card: &Card) {
+    fn post_redo_drag(
+        &mut self,
+        _origin_stack: &CardStack,
+        _destination_stack: &CardStack,
+        _move: &runtime::Move,
+    ) {
+    }
+
+    fn card_double_click(&mut self, card: &Card) {
         let card_stack = card.stack().unwrap();
         if card_stack.stack_type() == "foundation" {
             return;
@@ -149,15 +166,32 @@ impl Game for FreeCell {
         }
     }
 
-    fn stack_click(&self, _slot: &CardStack) {}
+    fn stack_click(&mut self, _slot: &CardStack) {}
+
+    fn can_click(&self, _slot: &CardStack) -> bool {
+        false
+    }
 
-    fn move_generator(&self) -> Box<dyn FnMut(&mut solver::State)> {
+    fn move_generator(&self) -> solver::MoveGenerator {
         Box::new(generate_solver_moves)
     }
 
-    fn is_won_fn(&self) -> Box<dyn FnMut(&mut solver::State) -> bool> {
+    fn is_won_fn(&self) -> runtime::WonFn {
         Box::new(is_won)
     }
+
+    fn foundation_moves(&self, state: &mut solver::State) -> Vec<(solver::Move, bool)> {
+        solver::foundation_moves(state, TO_FOUNDATION, FOUNDATION)
+    }
+
+    fn is_trivially_won(&self, state: &mut solver::State) -> bool {
+        // When no column has a card above a higher one, the lowest card left is always free
+        TABLEAU.iter().all(|i| {
+            state.get_stack(*i).windows(2).all(|pair| {
+                solver::solver_card_to_id(&pair[0]) % 13 >= solver::solver_card_to_id(&pair[1]) % 13
+            })
+        })
+    }
 }
 
 fn is_won(state: &mut solver::State) -> bool {
@@ -177,9 +211,6 @@ fn is_won(state: &mut solver::State) -> bool {
 
 #[rustfmt::skip]
 fn generate_solver_moves(state: &mut solver::State) {
-    const CELLS: &[usize] = &[0, 1, 2, 3];
-    const TABLEAU: &[usize] = &[8, 9, 10, 11, 12, 13, 14, 15];
-
     fn get_priority(state: &mut solver::State) -> usize {
         let mut outs = 0; // outs = number of cards that are out (in foundations)
         for (_i, outpile) in state.get_stacks(FOUNDATION) {
@@ -191,51 +222,13 @@ fn generate_solver_moves(state: &mut solver::State) {
         outs
     }
 
-    fn set_if_greater(a: &mut u8, b: &u8) {
-        if *a < *b {
-            *a = *b
-        }
-    }
-
     // Check for moves to foundation
-    for (i, tableau_card) in state.get_stacks_top(&[0, 1, 2, 3, 8, 9, 10, 11, 12, 13, 14, 15]) {
-        // from waste and tableau
-        let mut max_red = 0;
-        let mut max_black = 0;
-        let mut consider_moves = Vec::new();
-        for (j, foundation_stack) in state.get_stacks(FOUNDATION) {
-            if let Some(foundation_card) = foundation_stack.last() {
-                let rank_id = solver::solver_card_to_id(&foundation_card) % 13;
-                if solver::is_red(&foundation_card) { set_if_greater(&mut max_black, &(rank_id + 1)) }
-                else { set_if_greater(&mut max_red, &(rank_id + 1)) }
-                if solver::is_same_suit(&foundation_card, &tableau_card) && solver::is_one_rank_above(&foundation_card, &tableau_card) {
-                    consider_moves.push(solver::create_move(i, &tableau_card, j, MoveInstruction::None));
-                }
-            } else {
-                if solver::card_rank(&tableau_card) == "ace" {
-                    state.try_move(solver::create_move(i, &tableau_card, j, MoveInstruction::None), 100, get_priority, solver::no_onmove);
-                    return; // for performance reasons we suggest only automoves, if we find one
-                }
-            }
-        }
-        // Make sure automoves are safe
-        for move_option in consider_moves {
-            let card = move_option.card;
-            let rank_id = solver::solver_card_to_id(&card) % 13;
-            if solver::is_red(&card) {
-                // if the card rank is less than 3, moving it is probably not consequential
-                if rank_id <= max_red || rank_id < 2 {
-                    state.try_move(move_option, 100, get_priority, solver::no_onmove);
-                    return; // for performance reasons we suggest only automoves, if we find one
-                }
-            } else {
-                if rank_id <= max_black || rank_id < 2 {
-                    state.try_move(move_option, 100, get_priority, solver::no_onmove);
-                    return; // for performance reasons we suggest only automoves, if we find one
-                }
-            }
-            state.try_move(move_option, 3, get_priority, solver::no_onmove);
+    for (move_option, safe) in solver::foundation_moves(state, TO_FOUNDATION, FOUNDATION) {
+        if safe {
+            state.try_move(move_option, 100, get_priority, solver::no_onmove);
+            return; // for performance reasons we suggest only automoves, if we find one
         }
+        state.try_move(move_option, 3, get_priority, solver::no_onmove);
     }
 
     // Check for an open tableau
@@ -299,7 +292,7 @@ fn generate_solver_moves(state: &mut solver::State) {
     }
 }
 
-fn try_distribute(card: &Card, parent: &CardStack, game: &FreeCell) {
+fn try_distribute(card: &Card, parent: &CardStack, game: &mut FreeCell) {
     if !card.imp().is_face_up.get() {
         return;
     }
@@ -307,8 +300,9 @@ fn try_distribute(card: &Card, parent: &CardStack, game: &FreeCell) {
         return;
     }
 
+    let game_board = parent.game_board();
     for i in 0..4 {
-        let stack = runtime::get_stack(format!("foundation_{i}").as_str()).unwrap();
+        let stack = runtime::get_stack(&game_board, format!("foundation_{i}").as_str()).unwrap();
         if let Some(last_card) = stack.last_card() {
             if last_card.is_same_suit(card) && card.is_one_rank_above(&last_card) {
                 let mut move_ = runtime::create_move(
@@ -318,8 +312,8 @@ fn try_distribute(card: &Card, parent: &CardStack, game: &FreeCell) {
                     MoveInstruction::None,
                 );
                 runtime::perform_move_with_stacks(&mut move_, parent, &stack);
-                game.drag_completed(parent, &stack, &mut move_);
-                runtime::add_to_history(move_);
+                runtime::add_to_history(&game_board, &move_);
+                game.drag_completed(parent, &stack, &move_);
                 return;
             }
         } else {
@@ -331,8 +325,8 @@ fn try_distribute(card: &Card, parent: &CardStack, game: &FreeCell) {
                     MoveInstruction::None,
                 );
                 runtime::perform_move_with_stacks(&mut move_, parent, &stack);
-                game.drag_completed(parent, &stack, &mut move_);
-                runtime::add_to_history(move_);
+                runtime::add_to_history(&game_board, &move_);
+                game.drag_completed(parent, &stack, &move_);
                 return;
             }
         }
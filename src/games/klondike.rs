@@ -29,21 +29,59 @@ use crate::{
 use gtk::glib;
 use gtk::{prelude::*, subclass::prelude::*};
 
-pub struct Klondike {}
+pub struct Klondike {
+    n_deals: u8,
+}
+
+impl Klondike {
+    // Counts redeals and allows dropping once the stock and waste run out
+    fn track_stock(&mut self, origin_stack: &CardStack, destination_stack: &CardStack) {
+        if origin_stack.stack_type() == "waste" {
+            if destination_stack.stack_type() == "stock" {
+                self.n_deals += 1;
+            }
+            let game_board = origin_stack.game_board();
+            let stock = runtime::get_stack(&game_board, "stock").unwrap();
+            if stock.is_empty() && origin_stack.is_empty() {
+                runtime::set_can_drop(&game_board, true);
+            }
+        }
+    }
+}
+
+// Standard Klondike scoring
+fn move_points(origin_type: &str, destination_type: &str) -> i32 {
+    match (origin_type, destination_type) {
+        ("waste", "tableau") => 5,
+        ("waste" | "tableau", "foundation") => 10,
+        ("foundation", "tableau") => -15,
+        _ => 0,
+    }
+}
 
-impl Klondike {}
+const REVEAL_POINTS: i32 = 5;
 
+const TABLEAU: &[usize] = &[0, 1, 2, 3, 4, 5, 6];
 const FOUNDATION: &[usize] = &[7, 8, 9, 10];
+const WASTE: usize = 11;
+const STOCK: usize = 12;
+// The waste and the tableau, the stacks cards can be played home from
+const TO_FOUNDATION: &[usize] = &[11, 0, 1, 2, 3, 4, 5, 6];
 
 impl Game for Klondike {
-    fn new_game(mut cards: Vec<Card>, game_board: &GameBoard) -> Self {
+    fn new_game(
+        mut cards: Vec<Card>,
+        game_board: &GameBoard,
+        _options: &GameOptions,
+        rand: &mut Rand,
+    ) -> Self {
         let mut n_cards = cards.len() as i32;
 
         for i in 0..7 {
             let card_stack = CardStack::new("tableau", i, true);
 
             for j in 0..(i + 1) {
-                let random_card = glib::random_int_range(0, n_cards) as usize;
+                let random_card = rand.int_range(0, n_cards) as usize;
                 if let Some(card) = cards.get(random_card) {
                     card_stack.add_card(&card);
                     if j < i {
@@ -75,7 +113,7 @@ impl Game for Klondike {
         let stock = CardStack::new("stock", -1, false);
         stock.add_click();
         while n_cards > 0 {
-            let random_card = glib::random_int_range(0, n_cards) as usize;
+            let random_card = rand.int_range(0, n_cards) as usize;
             if let Some(card) = cards.get(random_card) {
                 stock.add_card(&card);
                 card.flip();
@@ -88,7 +126,7 @@ impl Game for Klondike {
         }
         game_board.add(&stock, 0, 0, 1, 1);
 
-        Self {}
+        Self { n_deals: 0 }
     }
     fn verify_drag(&self, bottom_card: &Card, _from_stack: &CardStack) -> bool {
         if !bottom_card.imp().is_face_up.get() {
@@ -132,50 +170,56 @@ impl Game for Klondike {
     }
 
     fn drag_completed(
-        &self,
+        &mut self,
         origin_stack: &CardStack,
         destination_stack: &CardStack,
-        move_: &mut runtime::Move,
+        _move: &runtime::Move,
     ) {
+        let game_board = origin_stack.game_board();
         let type_ = origin_stack.stack_type();
+        let points = move_points(&type_, &destination_stack.stack_type());
+        if points != 0 {
+            runtime::add_score(&game_board, points);
+        }
         if type_ == "tableau" {
             if let Some(last_card) = origin_stack.last_card() {
                 if !last_card.is_face_up() {
-                    move_.flip_index = Some(origin_stack.n_cards());
-                    last_card.flip();
+                    runtime::turn_card(&game_board, &last_card, true);
+                    runtime::add_score(&game_board, REVEAL_POINTS);
                 }
             }
         }
-        if type_ == "waste" {
-            let stock = runtime::get_stack("stock").unwrap();
-            if stock.is_empty() && origin_stack.is_empty() {
-                runtime::set_can_drop(true);
-            }
-        }
+        self.track_stock(origin_stack, destination_stack);
     }
 
     fn pre_undo_drag(
-        &self,
+        &mut self,
         origin_stack: &CardStack,
         dropped_stack: &CardStack,
-        move_: &mut runtime::Move,
+        _move: &runtime::Move,
     ) {
-        let type_ = origin_stack.stack_type();
-        if type_ == "tableau" {
-            if let Some(flip_index) = move_.flip_index {
-                origin_stack.get_card(flip_index).unwrap().flip();
+        if origin_stack.stack_type() == "waste" {
+            if dropped_stack.stack_type() == "stock" {
+                self.n_deals -= 1;
             }
-        } else if type_ == "stock" {
-            origin_stack.face_down_top_card();
-        } else if type_ == "waste" {
-            let stock = runtime::get_stack("stock").unwrap();
+            let game_board = origin_stack.game_board();
+            let stock = runtime::get_stack(&game_board, "stock").unwrap();
             if stock.is_empty() && origin_stack.is_empty() {
-                runtime::set_can_drop(false);
+                runtime::set_can_drop(&game_board, false);
             }
         }
     }
 
-    fn card_double_click(&self, card: &Card) {
+    fn post_redo_drag(
+        &mut self,
+        origin_stack: &CardStack,
+        destination_stack: &CardStack,
+        _move: &runtime::Move,
+    ) {
+        self.track_stock(origin_stack, destination_stack);
+    }
+
+    fn card_double_click(&mut self, card: &Card) {
         let card_stack = card.stack().unwrap();
         if card_stack.stack_type() == "foundation" {
             return;
@@ -184,18 +228,17 @@ impl Game for Klondike {
         }
     }
 
-    fn stack_click(&self, stack: &CardStack) {
+    fn stack_click(&mut self, stack: &CardStack) {
         if stack.stack_type() == "stock" {
-            let waste = runtime::get_stack("waste").unwrap();
+            let game_board = stack.game_board();
+            let waste = runtime::get_stack(&game_board, "waste").unwrap();
             if stack.is_empty() {
                 if waste.is_empty() {
                     return;
                 }
-                let n_deals = runtime::get_deals();
-                if n_deals >= 3 {
+                if self.n_deals >= 3 {
                     return;
                 }
-                runtime::update_deals(n_deals + 1);
                 //Fixme: Don't use widget_name
                 let mut move_ = runtime::create_move(
                     "waste",
@@ -203,32 +246,56 @@ impl Game for Klondike {
                     "stock",
                     MoveInstruction::Flip,
                 );
-                runtime::perform_move(&mut move_);
-                runtime::add_to_history(move_);
+                runtime::perform_move_with_stacks(&mut move_, &waste, stack);
+                runtime::add_to_history(&game_board, &move_);
+                self.drag_completed(&waste, stack, &move_);
             } else {
                 let card = stack.last_card().unwrap();
-                stack.remove_card(&card);
-                card.flip();
-                waste.add_card(&card);
-                waste.add_drag_to_card(&card);
-                card.remove_css_class("highlight");
-                runtime::add_to_history(runtime::create_move(
+                let mut move_ = runtime::create_move(
                     &stack.widget_name(),
                     &card.widget_name(),
                     "waste",
                     MoveInstruction::Flip,
-                ));
+                );
+                runtime::perform_move_with_stacks(&mut move_, stack, &waste);
+                runtime::remove_drag(&card);
+                waste.add_drag_to_card(&card);
+                runtime::add_to_history(&game_board, &move_);
             }
         }
     }
 
-    fn move_generator(&self) -> Box<dyn FnMut(&mut solver::State)> {
+    fn can_click(&self, stack: &CardStack) -> bool {
+        if stack.stack_type() != "stock" {
+            return false;
+        }
+        if !stack.is_empty() {
+            return true;
+        }
+        let waste = runtime::get_stack(&stack.game_board(), "waste").unwrap();
+        !waste.is_empty() && self.n_deals < 3
+    }
+
+    fn move_generator(&self) -> solver::MoveGenerator {
         Box::new(generate_solver_moves)
     }
 
-    fn is_won_fn(&self) -> Box<dyn FnMut(&mut solver::State) -> bool> {
+    fn is_won_fn(&self) -> runtime::WonFn {
         Box::new(is_won)
     }
+
+    fn foundation_moves(&self, state: &mut solver::State) -> Vec<(solver::Move, bool)> {
+        solver::foundation_moves(state, TO_FOUNDATION, FOUNDATION)
+    }
+
+    fn is_trivially_won(&self, state: &mut solver::State) -> bool {
+        // With nothing left to draw and no hidden cards, every column is in sequence
+        state.get_stack(STOCK).is_empty()
+            && state.get_stack(WASTE).is_empty()
+            && TABLEAU
+                .iter()
+                .all(|i| !state.get_stack(*i).iter().any(solver::is_flipped))
+    }
 }
 
 fn is_won(state: &mut solver::State) -> bool {
@@ -248,10 +315,6 @@ fn is_won(state: &mut solver::State) -> bool {
 
 #[rustfmt::skip]
 fn generate_solver_moves(state: &mut solver::State) {
-    const STOCK:usize = 12;
-    const WASTE:usize = 11;
-    const TABLEAU:&[usize] = &[0, 1, 2, 3, 4, 5, 6];
-
     fn get_priority(state: &mut solver::State) -> usize {
         let mut outs = 0; // outs = number of cards that are out (in foundations)
         for (_i, outpile) in state.get_stacks(FOUNDATION) {
@@ -260,23 +323,19 @@ fn generate_solver_moves(state: &mut solver::State) {
         outs
     }
 
-    fn set_if_greater(a: &mut u8, b: &u8) {
-        if *a < *b { *a = *b }
-    }
-
     fn onmove(move_option: &mut solver::Move, state: &mut solver::State, undo: bool) {
         if TABLEAU.contains(&move_option.origin_stack) {
-            let origin_stack = state.get_stack_mut(move_option.origin_stack);
             if undo {
-                if let Some(flip_index) = move_option.flip_index {
-                    let card = origin_stack.get_mut(flip_index).unwrap();
-                    solver::flip(card);
+                for (stack, index) in move_option.flips.drain(..) {
+                    solver::flip(&mut state.get_stack_mut(stack)[index]);
                 }
             } else {
+                let origin_stack = state.get_stack_mut(move_option.origin_stack);
                 if let Some(card) = origin_stack.last_mut() {
                     if solver::is_flipped(&card) {
                         solver::flip(card);
-                        move_option.flip_index = Some(origin_stack.len() - 1);
+                        let index = origin_stack.len() - 1;
+                        move_option.flips.push((move_option.origin_stack, index));
                     }
                 }
             }
@@ -284,44 +343,12 @@ fn generate_solver_moves(state: &mut solver::State) {
     }
 
     // Check for moves to foundation
-    for (i, tableau_card) in state.get_stacks_top(&[11, 0, 1, 2, 3, 4, 5, 6]) { // from waste and tableau
-        if solver::is_flipped(&tableau_card) { continue } // this should never happen anyways
-        let mut max_red = 0;
-        let mut max_black = 0;
-        let mut consider_moves = Vec::new();
-        for (j, foundation_stack) in state.get_stacks(FOUNDATION) {
-            if let Some(foundation_card) = foundation_stack.last() {
-                let rank_id = solver::solver_card_to_id(&foundation_card) % 13;
-                if solver::is_red(&foundation_card) { set_if_greater(&mut max_black, &(rank_id + 1)) }
-                else { set_if_greater(&mut max_red, &(rank_id + 1)) }
-                if solver::is_same_suit(&foundation_card, &tableau_card) && solver::is_one_rank_above(&foundation_card, &tableau_card) {
-                    consider_moves.push(solver::create_move(i, &tableau_card, j, MoveInstruction::None));
-                }
-            } else {
-                if solver::card_rank(&tableau_card) == "ace" {
-                    state.try_move(solver::create_move(i, &tableau_card, j, MoveInstruction::None), 100, get_priority, onmove);
-                    return; // for performance reasons we suggest only automoves, if we find one
-                }
-            }
-        }
-        // Make sure automoves are safe
-        for move_option in consider_moves {
-            let card = move_option.card;
-            let rank_id = solver::solver_card_to_id(&card) % 13;
-            if solver::is_red(&card) {
-                // if the card rank is less than 3, moving it is probably not consequential
-                if rank_id <= max_red || rank_id < 2 {
-                    state.try_move(move_option, 100, get_priority, onmove);
-                    return; // for performance reasons we suggest only automoves, if we find one
-                }
-            } else {
-                if rank_id <= max_black || rank_id < 2 {
-                    state.try_move(move_option, 100, get_priority, onmove);
-                    return; // for performance reasons we suggest only automoves, if we find one
-                }
-            }
-            state.try_move(move_option, 3, get_priority, onmove);
+    for (move_option, safe) in solver::foundation_moves(state, TO_FOUNDATION, FOUNDATION) {
+        if safe {
+            state.try_move(move_option, 100, get_priority, onmove);
+            return; // for performance reasons we suggest only automoves, if we find one
         }
+        state.try_move(move_option, 3, get_priority, onmove);
     }
 
     let stock = state.get_stack_owned(STOCK);
@@ -375,7 +402,7 @@ fn generate_solver_moves(state: &mut solver::State) {
     }
 }
 
-fn try_distribute(card: &Card, parent: &CardStack, game: &Klondike) {
+fn try_distribute(card: &Card, parent: &CardStack, game: &mut Klondike) {
     if !card.imp().is_face_up.get() {
         return;
     }
@@ -383,8 +410,9 @@ fn try_distribute(card: &Card, parent: &CardStack, game: &Klondike) {
         return;
     }
 
+    let game_board = parent.game_board();
     for i in 0..4 {
-        let stack = runtime::get_stack(format!("foundation_{i}").as_str()).unwrap();
+        let stack = runtime::get_stack(&game_board, format!("foundation_{i}").as_str()).unwrap();
         if let Some(last_card) = stack.last_card() {
             if last_card.is_same_suit(card) && card.is_one_rank_above(&last_card) {
                 let mut move_ = runtime::create_move(
@@ -394,8 +422,8 @@ fn try_distribute(card: &Card, parent: &CardStack, game: &Klondike) {
                     MoveInstruction::None,
                 );
                 runtime::perform_move_with_stacks(&mut move_, parent, &stack);
-                game.drag_completed(parent, &stack, &mut move_);
-                runtime::add_to_history(move_);
+                runtime::add_to_history(&game_board, &move_);
+                game.drag_completed(parent, &stack, &move_);
                 return;
             }
         } else {
@@ -407,8 +435,8 @@ fn try_distribute(card: &Card, parent: &CardStack, game: &Klondike) {
                     MoveInstruction::None,
                 );
                 runtime::perform_move_with_stacks(&mut move_, parent, &stack);
-                game.drag_completed(parent, &stack, &mut move_);
-                runtime::add_to_history(move_);
+                runtime::add_to_history(&game_board, &move_);
+                game.drag_completed(parent, &stack, &move_);
                 return;
             }
         }
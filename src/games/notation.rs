@@ -0,0 +1,165 @@
+/* notation.rs
+ *
+ * Copyright 2026 Will Warner
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! A short text notation for positions, one stack per line with its cards
+//! from the bottom up:
+//!
+//! ```text
+//! tableau_0: ~7C ~2D KH
+//! foundation_0: AS
+//! waste:
+//! ```
+//!
+//! A card is its rank (`A`, `2`–`9`, `T`, `J`, `Q`, `K`) followed by its
+//! suit (`C`, `D`, `H`, `S`), and `RJ` or `BJ` for the jokers. Face-down
+//! cards start with `~`. Blank lines and lines starting with `#` are ignored.
+
+use super::solver;
+use gettextrs::gettext;
+
+const RANKS: &str = "A23456789TJQK";
+const SUITS: &str = "CDHS";
+const JOKERS: [&str; 2] = ["RJ", "BJ"];
+const FACE_DOWN: char = '~';
+
+/// The notation of a solver card, like `~KH` for the king of hearts face down
+pub fn card_to_notation(card: u8) -> String {
+    let id = solver::solver_card_to_id(&card);
+    let mut notation = String::new();
+    if solver::is_flipped(&card) {
+        notation.push(FACE_DOWN);
+    }
+    match id {
+        0..=51 => {
+            notation.push(RANKS.as_bytes()[usize::from(id % 13)].into());
+            notation.push(SUITS.as_bytes()[usize::from(id / 13)].into());
+        }
+        _ => notation.push_str(
+            usize::from(id)
+                .checked_sub(53)
+                .and_then(|joker| JOKERS.get(joker))
+                .unwrap_or(&"??"),
+        ),
+    }
+    notation
+}
+
+pub fn notation_to_card(notation: &str) -> Option<u8> {
+    let (face_down, card) = match notation.strip_prefix(FACE_DOWN) {
+        Some(card) => (true, card),
+        None => (false, notation),
+    };
+    let id = match JOKERS.iter().position(|joker| *joker == card) {
+        Some(joker) => 53 + joker as u8,
+        None => {
+            let mut chars = card.chars();
+            let rank = RANKS.find(chars.next()?.to_ascii_uppercase())?;
+            let suit = SUITS.find(chars.next()?.to_ascii_uppercase())?;
+            if chars.next().is_some() {
+                return None;
+            }
+            (suit * 13 + rank) as u8
+        }
+    };
+    Some(if face_down { id | 0x80 } else { id })
+}
+
+/// Writes out a position, one stack to a line
+pub fn format_position(stack_names: &[String], game_state: &[Vec<u8>]) -> String {
+    let mut text = String::new();
+    for (name, stack) in stack_names.iter().zip(game_state) {
+        text.push_str(name);
+        text.push(':');
+        for card in stack {
+            text.push(' ');
+            text.push_str(&card_to_notation(*card));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Reads a position back, as the stack names with their solver cards
+pub fn parse_position(text: &str) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut position = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let unreadable = || {
+            // Translators: {line} is a line number in a position copied as text
+            gettext("Line {line} of the position can't be read")
+                .replace("{line}", &(n + 1).to_string())
+        };
+        let (name, cards) = line.split_once(':').ok_or_else(unreadable)?;
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(unreadable());
+        }
+        let cards = cards
+            .split_whitespace()
+            .map(notation_to_card)
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(unreadable)?;
+        position.push((name.to_string(), cards));
+    }
+    if position.is_empty() {
+        return Err(gettext("The text is not a position"));
+    }
+    Ok(position)
+}
+
+/// Writes every card out and reads it back, face up and face down
+pub fn test_notation() {
+    for id in (0..52).chain(53..55) {
+        for card in [id, id | 0x80] {
+            let notation = card_to_notation(card);
+            assert_eq!(
+                notation_to_card(&notation),
+                Some(card),
+                "Notation mismatch for {card}: {notation}"
+            );
+        }
+    }
+    assert_eq!(
+        card_to_notation(solver::card_name_to_solver("heart_king", true)),
+        "~KH"
+    );
+    assert_eq!(
+        notation_to_card("TC"),
+        Some(solver::card_name_to_solver("club_10", false))
+    );
+
+    let stack_names = vec!["tableau_0".to_string(), "waste".to_string()];
+    let game_state = vec![
+        vec![
+            solver::card_name_to_solver("club_7", true),
+            solver::card_name_to_solver("spade_ace", false),
+        ],
+        Vec::new(),
+    ];
+    let text = format_position(&stack_names, &game_state);
+    assert_eq!(
+        parse_position(&text),
+        Ok(stack_names.into_iter().zip(game_state).collect()),
+        "Position mismatch for\n{text}"
+    );
+}
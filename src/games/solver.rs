@@ -27,7 +27,8 @@ pub struct Move {
     pub card: u8,
     pub destination_stack: usize,
     pub instruction: MoveInstruction,
-    pub flip_index: Option<usize>,
+    /// Cards turned face up as a consequence of the move, as (stack, index) pairs
+    pub flips: Vec<(usize, usize)>,
 }
 
 // NOTE: IndexMap will panic if origin_stack and destination_stack are the same.
@@ -151,8 +152,9 @@ pub fn is_similar_suit(card_1: &u8, card_2: &u8) -> bool {
 }
 
 pub fn is_red(card: &u8) -> bool {
+    // Diamonds and hearts, in the order of `SUITES`
     let suit = solver_card_to_id(card) / 13;
-    suit == 1 || suit == 3
+    suit == 1 || suit == 2
 }
 
 pub fn card_rank(card: &u8) -> &str {
@@ -160,6 +162,51 @@ pub fn card_rank(card: &u8) -> &str {
     RANKS[rank as usize]
 }
 
+/// Finds the moves of the top cards of `from_stacks` onto `foundations`, and
+/// whether each one is safe to make automatically: aces, twos, and cards whose
+/// rank both suits of the other colour are built up to at least one below, so
+/// that no card still out could need them to be built on.
+pub fn foundation_moves(
+    state: &mut State,
+    from_stacks: &[usize],
+    foundations: &[usize],
+) -> Vec<(Move, bool)> {
+    // How many cards of each suit are on the foundations
+    let mut built = [0u8; 4];
+    for (_j, foundation_card) in state.get_stacks_top(foundations) {
+        let id = solver_card_to_id(&foundation_card);
+        let suit = usize::from(id / 13);
+        built[suit] = std::cmp::max(built[suit], id % 13 + 1);
+    }
+
+    let mut moves = Vec::new();
+    for (i, card) in state.get_stacks_top(from_stacks) {
+        if is_flipped(&card) {
+            continue;
+        }
+        for (j, foundation_stack) in state.get_stacks(foundations) {
+            if let Some(foundation_card) = foundation_stack.last() {
+                if is_same_suit(foundation_card, &card) && is_one_rank_above(foundation_card, &card)
+                {
+                    let rank_id = solver_card_to_id(&card) % 13;
+                    // The least built suit of the other colour decides
+                    let other_colour = (0..4u8)
+                        .filter(|suit| is_red(&(suit * 13)) != is_red(&card))
+                        .map(|suit| built[usize::from(suit)])
+                        .min()
+                        .unwrap_or(0);
+                    let safe = rank_id < 2 || rank_id <= other_colour;
+                    moves.push((create_move(i, &card, j, MoveInstruction::None), safe));
+                }
+            } else if card_rank(&card) == "ace" {
+                moves.push((create_move(i, &card, j, MoveInstruction::None), true));
+                break;
+            }
+        }
+    }
+    moves
+}
+
 pub fn create_move(
     origin_stack: usize,
     card: &u8,
@@ -171,12 +218,16 @@ pub fn create_move(
         card: card.to_owned(),
         destination_stack,
         instruction,
-        flip_index: None,
+        flips: Vec::new(),
     }
 }
 
 use indexmap::IndexSet;
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Expands a solver state by calling `State::try_move` for every move worth considering
+pub type MoveGenerator = Box<dyn FnMut(&mut State) + Send>;
 
 pub struct State {
     game_state: Vec<Vec<u8>>,
@@ -278,20 +329,32 @@ pub fn new_ghost_state(game_state: Vec<Vec<u8>>) -> State {
     }
 }
 
-static SHOULD_STOP: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
-
-pub fn set_should_stop(should_stop: bool) {
-    SHOULD_STOP.store(should_stop, std::sync::atomic::Ordering::SeqCst);
-}
-
-pub fn get_should_stop() -> bool {
-    SHOULD_STOP.load(std::sync::atomic::Ordering::Relaxed)
+/// The moves the generator comes up with for a state, in the order the solver
+/// would try them
+pub fn candidate_moves(game_state: Vec<Vec<u8>>, move_generator: &mut MoveGenerator) -> Vec<Move> {
+    let mut state = State {
+        game_state,
+        states: IndexSet::new(),
+        nodes: Vec::new(),
+        queues: vec![VecDeque::new(); 53],
+        q_index: 0,
+        parent_node: None,
+    };
+    move_generator(&mut state);
+    state
+        .queues
+        .iter()
+        .rev()
+        .flatten()
+        .map(|node_index| state.nodes[*node_index].move_option.clone())
+        .collect()
 }
 
 pub(super) fn solve<M: FnMut(&mut State), W: FnMut(&mut State) -> bool>(
     game_state: Vec<Vec<u8>>,
     mut move_generator: M,
     mut is_won_fn: W,
+    should_stop: &AtomicBool,
 ) -> Option<Vec<Move>> {
     let mut state = State {
         game_state,
@@ -311,7 +374,7 @@ pub(super) fn solve<M: FnMut(&mut State), W: FnMut(&mut State) -> bool>(
 
     while expanded < 15_000 {
         if expanded % 200 == 0 {
-            if get_should_stop() {
+            if should_stop.load(Ordering::Relaxed) {
                 return None;
             }
         }
@@ -378,7 +441,7 @@ pub(super) fn solve<M: FnMut(&mut State), W: FnMut(&mut State) -> bool>(
 
 #[cfg(feature = "solver-debug")]
 pub(super) fn solver_debug(
-    parent: &crate::window::SolitaireWindow,
+    game_board: &GameBoard,
     game_state: Vec<Vec<u8>>,
     stack_names: Vec<String>,
 ) {
@@ -436,15 +499,8 @@ pub(super) fn solver_debug(
                 row.connect_activated(move |_| {
                     let mut state = DEBUG_STATE.take().unwrap();
                     state.game_state = state.states[state_key].clone();
-                    let mut text = String::new();
                     let stack_names = DEBUG_STACK_NAMES.with(|v| v.borrow().clone());
-                    for i in 0..state.game_state.len() {
-                        text.push_str(&format!("{}: ", stack_names[i]));
-                        for card_id in &state.game_state[i] {
-                            text.push_str(&format!("{}, ", solver_card_to_name(*card_id)));
-                        }
-                        text.push_str("\n\n");
-                    }
+                    let text = super::notation::format_position(&stack_names, &state.game_state);
                     view_ref.buffer().set_text(&text);
                     DEBUG_STATE.set(Some(state));
                 });
@@ -453,17 +509,17 @@ pub(super) fn solver_debug(
         };
 
     {
-        let mut game = CURRENT_GAME.lock().unwrap();
-        let game = game.as_mut().expect("solver_debug: failed to get game");
+        let mut move_generator = with_game(game_board, |game| game.move_generator())
+            .expect("solver_debug: failed to get game");
         let mut state = DEBUG_STATE.take().unwrap();
-        game.move_generator()(&mut state);
+        move_generator(&mut state);
         DEBUG_STATE.set(Some(state));
         make_node_rows(&node_list, &node_view, 0);
     }
 
+    let board = game_board.clone();
     expand_button.connect_clicked(move |button| {
-        let mut game = CURRENT_GAME.lock().unwrap();
-        if let Some(game) = game.as_mut() {
+        if let Some(mut move_generator) = with_game(&board, |game| game.move_generator()) {
             let n_expand = button
                 .ancestor(adw::SpinRow::static_type())
                 .unwrap()
@@ -473,7 +529,7 @@ pub(super) fn solver_debug(
             for _ in 0..n_expand {
                 let mut state = DEBUG_STATE.take().unwrap();
                 let node_index = state.nodes.len();
-                game.move_generator()(&mut state);
+                move_generator(&mut state);
                 if let Some(node) = state.nodes.last() {
                     state.game_state = state.states[node.state_key].clone();
                 }
@@ -486,6 +542,6 @@ pub(super) fn solver_debug(
     let main_loop = glib::MainLoop::new(None, false);
     let main_loop_ref = main_loop.clone();
     dialog.connect_closed(move |_| main_loop_ref.quit());
-    dialog.present(Some(parent));
+    dialog.present(game_board.window().as_ref());
     main_loop.run();
 }
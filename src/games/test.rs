@@ -34,7 +34,12 @@ pub struct Test {}
 const FOUNDATION: usize = 2;
 
 impl Game for Test {
-    fn new_game(mut cards: Vec<Card>, game_board: &GameBoard) -> Self {
+    fn new_game(
+        mut cards: Vec<Card>,
+        game_board: &GameBoard,
+        _options: &GameOptions,
+        _rand: &mut Rand,
+    ) -> Self {
         cards.sort_by(|a, b| a.imp().card_id.get().cmp(&b.imp().card_id.get()));
         let stock = CardStack::new("stock", -1, false);
         for _ in 0..52 {
@@ -86,27 +91,36 @@ impl Game for Test {
     }
 
     fn drag_completed(
-        &self,
+        &mut self,
         _origin_stack: &CardStack,
         _destination_stack: &CardStack,
-        _move: &mut runtime::Move,
+        _move: &runtime::Move,
     ) {
     }
 
     fn pre_undo_drag(
-        &self,
+        &mut self,
         _previous_origin_stack: &CardStack,
         _previous_destination_stack: &CardStack,
-        _move: &mut runtime::Move,
+        _move: &runtime::Move,
     ) {
     }
 
-    fn card_double_click(&self, card: &Card) {
+    fn post_redo_drag(
+        &mut self,
+        _origin_stack: &CardStack,
+        _destination_stack: &CardStack,
+        _move: &runtime::Move,
+    ) {
+    }
+
+    fn card_double_click(&mut self, card: &Card) {
         let card_stack = card.stack().unwrap();
         if card_stack.stack_type() == "waste" {
             if let Some(top_card) = card_stack.last_card() {
                 let mut perform_move = false;
-                let foundation = runtime::get_stack("foundation").unwrap();
+                let foundation =
+                    runtime::get_stack(&card_stack.game_board(), "foundation").unwrap();
                 if top_card.rank() == "ace" {
                     perform_move = true
                 } else if let Some(foundation_top) = &foundation.last_card() {
@@ -122,16 +136,17 @@ impl Game for Test {
                         MoveInstruction::None,
                     );
                     runtime::perform_move_with_stacks(&mut move_, &card_stack, &foundation);
-                    self.drag_completed(&card_stack, &foundation, &mut move_);
-                    runtime::add_to_history(move_);
+                    runtime::add_to_history(&card_stack.game_board(), &move_);
+                    self.drag_completed(&card_stack, &foundation, &move_);
                 }
             }
         }
     }
 
-    fn stack_click(&self, slot: &CardStack) {
+    fn stack_click(&mut self, slot: &CardStack) {
         if slot.stack_type() == "stock" {
-            let waste = runtime::get_stack("waste").unwrap();
+            let game_board = slot.game_board();
+            let waste = runtime::get_stack(&game_board, "waste").unwrap();
             if let Some(top_card) = slot.last_card() {
                 let mut move_ = runtime::create_move(
                     "stock",
@@ -140,20 +155,32 @@ impl Game for Test {
                     MoveInstruction::Flip,
                 );
                 runtime::perform_move_with_stacks(&mut move_, slot, &waste);
-                self.drag_completed(slot, &waste, &mut move_);
-                runtime::add_to_history(move_);
+                runtime::add_to_history(&game_board, &move_);
+                self.drag_completed(slot, &waste, &move_);
                 waste.add_drag_to_card(&top_card);
             }
         }
     }
 
-    fn move_generator(&self) -> Box<dyn FnMut(&mut solver::State)> {
+    fn can_click(&self, slot: &CardStack) -> bool {
+        slot.stack_type() == "stock" && !slot.is_empty()
+    }
+
+    fn move_generator(&self) -> solver::MoveGenerator {
         Box::new(generate_solver_moves)
     }
 
-    fn is_won_fn(&self) -> Box<dyn FnMut(&mut solver::State) -> bool> {
+    fn is_won_fn(&self) -> runtime::WonFn {
         Box::new(is_won)
     }
+
+    fn foundation_moves(&self, _state: &mut solver::State) -> Vec<(solver::Move, bool)> {
+        Vec::new()
+    }
+
+    fn is_trivially_won(&self, _state: &mut solver::State) -> bool {
+        false
+    }
 }
 
 fn is_won(state: &mut solver::State) -> bool {
@@ -25,9 +25,7 @@ use crate::{
     game_board::GameBoard,
     runtime,
     runtime::MoveInstruction,
-    window,
 };
-use gtk::glib;
 use gtk::{prelude::*, subclass::prelude::*};
 
 pub struct TriPeaks {}
@@ -35,11 +33,16 @@ pub struct TriPeaks {}
 impl TriPeaks {}
 
 impl Game for TriPeaks {
-    fn new_game(mut cards: Vec<Card>, game_board: &GameBoard) -> Self {
+    fn new_game(
+        mut cards: Vec<Card>,
+        game_board: &GameBoard,
+        _options: &GameOptions,
+        rand: &mut Rand,
+    ) -> Self {
         let mut n_cards = cards.len() as i32;
         let mut add_pyramid = |col: f64, row: f64, flip: bool| {
             let card_stack = CardStack::new("pyramid", 52 - n_cards, false);
-            let random_card = glib::random_int_range(0, n_cards) as usize;
+            let random_card = rand.int_range(0, n_cards) as usize;
             let card = &cards[random_card];
             card_stack.add_card(&card);
             if flip {
@@ -75,7 +78,7 @@ impl Game for TriPeaks {
         let stock = CardStack::new("stock", -1, false);
         stock.add_click();
         while n_cards > 0 {
-            let random_card = glib::random_int_range(0, n_cards) as usize;
+            let random_card = rand.int_range(0, n_cards) as usize;
             let card = &cards[random_card];
             stock.add_card(&card);
             card.flip();
@@ -112,10 +115,10 @@ impl Game for TriPeaks {
     }
 
     fn drag_completed(
-        &self,
+        &mut self,
         origin_stack: &CardStack,
         _destination_stack: &CardStack,
-        move_: &mut runtime::Move,
+        _move: &runtime::Move,
     ) {
         fn try_flip(stack: &CardStack, above: i32) {
             if above == -1 {
@@ -132,18 +135,17 @@ impl Game for TriPeaks {
                 .first_card()
                 .is_none()
             {
-                runtime::get_stack(&format!("pyramid_{above}"))
+                let game_board = stack.game_board();
+                let card = runtime::get_stack(&game_board, &format!("pyramid_{above}"))
                     .expect(&format!("tri_peaks: couldn't get pyramid_{above}"))
-                    .face_up_top_card();
+                    .last_card()
+                    .expect(&format!("tri_peaks: pyramid_{above} is empty"));
+                runtime::turn_card(&game_board, &card, true);
             }
         }
 
         if origin_stack.stack_type() == "pyramid" {
-            window::SolitaireWindow::get_window()
-                .unwrap()
-                .get_gameboard()
-                .send_to_back(origin_stack);
-            origin_stack.set_can_target(false); // Force GTK to consider other stacks for dragging
+            clear_pyramid_slot(origin_stack);
             let num: i32 = origin_stack
                 .widget_name()
                 .split_once('_')
@@ -172,96 +174,71 @@ impl Game for TriPeaks {
     }
 
     fn pre_undo_drag(
-        &self,
+        &mut self,
         origin_stack: &CardStack,
         _dropped_stack: &CardStack,
-        move_: &mut runtime::Move,
+        _move: &runtime::Move,
     ) {
-        fn try_unflip(stack: &CardStack, above: i32) {
-            if above == -1 {
-                return;
-            }
-            if stack.first_card().is_some() {
-                return;
-            }
-            if stack
-                .next_sibling()
-                .unwrap()
-                .downcast::<CardStack>()
-                .unwrap()
-                .first_card()
-                .is_none()
-            {
-                runtime::get_stack(&format!("pyramid_{above}"))
-                    .expect(&format!("tri_peaks: couldn't get pyramid_{above}"))
-                    .face_down_top_card();
-            }
-        }
         if origin_stack.stack_type() == "pyramid" {
-            window::SolitaireWindow::get_window()
-                .unwrap()
-                .get_gameboard()
-                .reset_position(origin_stack);
+            origin_stack.game_board().reset_position(origin_stack);
             origin_stack.set_can_target(true);
-            let num: i32 = origin_stack
-                .widget_name()
-                .split_once('_')
-                .unwrap()
-                .1
-                .parse()
-                .unwrap();
-
-            if let Some(prev) = origin_stack.prev_sibling() {
-                let above = get_above(
-                    prev.widget_name()
-                        .split_once('_')
-                        .unwrap()
-                        .1
-                        .parse()
-                        .unwrap(),
-                );
-                try_unflip(&prev.downcast().unwrap(), above);
-            }
+        }
+    }
 
-            if !(num == 27 || num == 17 || num == 8 || num == 6 || num == 4) {
-                let above = get_above(num as usize);
-                try_unflip(origin_stack, above);
-            }
+    fn post_redo_drag(
+        &mut self,
+        origin_stack: &CardStack,
+        _destination_stack: &CardStack,
+        _move: &runtime::Move,
+    ) {
+        if origin_stack.stack_type() == "pyramid" {
+            clear_pyramid_slot(origin_stack);
         }
     }
 
-    fn card_double_click(&self, _card: &Card) {}
+    fn card_double_click(&mut self, _card: &Card) {}
 
-    fn stack_click(&self, slot: &CardStack) {
+    fn stack_click(&mut self, slot: &CardStack) {
         if slot.stack_type() == "stock" {
-            let waste = runtime::get_stack("waste").unwrap();
+            let game_board = slot.game_board();
+            let waste = runtime::get_stack(&game_board, "waste").unwrap();
 
             if slot.is_empty() {
                 return;
             } else {
                 let card = slot.last_card().unwrap();
-                slot.remove_card(&card);
-                card.flip();
-                waste.add_card(&card);
-                waste.add_drag_to_card(&card);
-                card.remove_css_class("highlight");
-                runtime::add_to_history(runtime::create_move(
+                let mut move_ = runtime::create_move(
                     &slot.widget_name(),
                     &card.widget_name(),
                     "waste",
                     MoveInstruction::Flip,
-                ));
+                );
+                runtime::perform_move_with_stacks(&mut move_, slot, &waste);
+                waste.add_drag_to_card(&card);
+                runtime::add_to_history(&game_board, &move_);
             }
         }
     }
 
-    fn move_generator(&self) -> Box<dyn FnMut(&mut solver::State)> {
+    fn can_click(&self, slot: &CardStack) -> bool {
+        slot.stack_type() == "stock" && !slot.is_empty()
+    }
+
+    fn move_generator(&self) -> solver::MoveGenerator {
         Box::new(generate_solver_moves)
     }
 
-    fn is_won_fn(&self) -> Box<dyn FnMut(&mut solver::State) -> bool> {
+    fn is_won_fn(&self) -> runtime::WonFn {
         Box::new(is_won)
     }
+
+    fn foundation_moves(&self, _state: &mut solver::State) -> Vec<(solver::Move, bool)> {
+        Vec::new()
+    }
+
+    fn is_trivially_won(&self, _state: &mut solver::State) -> bool {
+        false
+    }
 }
 
 fn is_won(state: &mut solver::State) -> bool {
@@ -297,6 +274,11 @@ fn get_above(index: usize) -> i32 {
     }
 }
 
+fn clear_pyramid_slot(stack: &CardStack) {
+    stack.game_board().send_to_back(stack);
+    stack.set_can_target(false); // Force GTK to consider other stacks for dragging
+}
+
 fn generate_solver_moves(state: &mut solver::State) {
     const WASTE: usize = 28;
     fn get_priority(state: &mut solver::State) -> usize {
@@ -321,14 +303,17 @@ fn generate_solver_moves(state: &mut solver::State) {
             }
             if state.get_stack(num + 1).is_empty() {
                 let above = above as usize;
-                solver::flip(state.get_stack_mut(above).last_mut().unwrap());
+                let above_stack = state.get_stack_mut(above);
+                solver::flip(above_stack.last_mut().unwrap());
+                move_.flips.push((above, above_stack.len() - 1));
+            }
+        }
 
-                if let Some(flip) = move_.flip_index {
-                    move_.flip_index = Some(flip + above);
-                } else {
-                    move_.flip_index = Some(above);
-                }
+        if undo {
+            for (stack, index) in move_option.flips.drain(..) {
+                solver::flip(&mut state.get_stack_mut(stack)[index]);
             }
+            return;
         }
 
         if move_option.origin_stack < 28 {
@@ -0,0 +1,207 @@
+/* keyboard.rs
+ *
+ * Copyright 2026 Will Warner
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use crate::{card::Card, card_stack::CardStack, game_board::GameBoard, games};
+use gtk::prelude::*;
+use gtk::{gdk, glib};
+
+/// Lets cards be picked up with Space or Enter and put down on the focused
+/// stack the same way, with the arrow keys moving between stacks
+pub fn add_controller(game_board: &GameBoard) {
+    let keys = gtk::EventControllerKey::new();
+    keys.connect_key_pressed(glib::clone!(
+        #[weak]
+        game_board,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, keyval, _, state| key_pressed(&game_board, keyval, state)
+    ));
+    game_board.add_controller(keys);
+}
+
+fn key_pressed(
+    game_board: &GameBoard,
+    keyval: gdk::Key,
+    state: gdk::ModifierType,
+) -> glib::Propagation {
+    // Leave shortcuts like Ctrl+Z to the window
+    if state.intersects(gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::ALT_MASK) {
+        return glib::Propagation::Proceed;
+    }
+    let Some((stack, card)) = focused(game_board) else {
+        return glib::Propagation::Proceed;
+    };
+    match keyval {
+        gdk::Key::space | gdk::Key::Return | gdk::Key::KP_Enter | gdk::Key::ISO_Enter => {
+            // Cards are still moving into place
+            if game_board.can_target() {
+                activate(game_board, &stack, card.as_ref());
+            }
+        }
+        gdk::Key::Escape if !game_board.selection().borrow().is_empty() => {
+            clear_selection(game_board);
+        }
+        gdk::Key::Left | gdk::Key::KP_Left => move_focus(&stack, None, gtk::DirectionType::Left),
+        gdk::Key::Right | gdk::Key::KP_Right => move_focus(&stack, None, gtk::DirectionType::Right),
+        gdk::Key::Up | gdk::Key::KP_Up => move_focus(&stack, card, gtk::DirectionType::Up),
+        gdk::Key::Down | gdk::Key::KP_Down => move_focus(&stack, card, gtk::DirectionType::Down),
+        _ => return glib::Propagation::Proceed,
+    }
+    glib::Propagation::Stop
+}
+
+// The stack with the keyboard focus, and the focused card in it if it isn't empty
+fn focused(game_board: &GameBoard) -> Option<(CardStack, Option<Card>)> {
+    let widget = game_board.root()?.focus()?;
+    match widget.downcast::<Card>() {
+        Ok(card) => Some((card.stack()?, Some(card))),
+        Err(widget) => Some((widget.downcast::<CardStack>().ok()?, None)),
+    }
+}
+
+/// Picks up the card, or puts the picked up cards down on the stack
+pub fn activate(game_board: &GameBoard, stack: &CardStack, card: Option<&Card>) {
+    let selected = game_board.selection().borrow().first().cloned();
+    if let Some(selected) = selected {
+        clear_selection(game_board);
+        if card == Some(&selected) {
+            return;
+        }
+        if move_cards(&selected, stack) {
+            selected.grab_focus();
+        } else {
+            game_board.error_bell();
+        }
+    } else if stack.stack_type() == "stock" {
+        games::stack_click(stack);
+        focus_stack(stack);
+    } else if let Some(card) = card.filter(|card| games::verify_drag(card, stack)) {
+        select(game_board, card);
+    } else {
+        game_board.error_bell();
+    }
+}
+
+// Marks the card and every card on top of it as picked up
+fn select(game_board: &GameBoard, card: &Card) {
+    let mut selection = game_board.selection().borrow_mut();
+    let mut next = Some(card.clone().upcast::<gtk::Widget>());
+    while let Some(widget) = next {
+        next = widget.next_sibling();
+        if let Ok(card) = widget.downcast::<Card>() {
+            card.add_css_class("selected");
+            selection.push(card);
+        }
+    }
+}
+
+/// Puts down the cards picked up from the keyboard without moving them
+pub fn clear_selection(game_board: &GameBoard) {
+    for card in game_board.selection().take() {
+        card.remove_css_class("selected");
+    }
+}
+
+/// Moves the card and the cards on top of it onto the stack if the game allows it
+pub fn move_cards(selected: &Card, to_stack: &CardStack) -> bool {
+    let Some(origin) = selected.stack() else {
+        return false;
+    };
+    if &origin == to_stack {
+        return false;
+    }
+    let game_board = origin.game_board();
+    let mut moving = Vec::new();
+    let mut next = Some(selected.clone().upcast::<gtk::Widget>());
+    while let Some(widget) = next {
+        next = widget.next_sibling();
+        moving.extend(widget.downcast::<Card>().ok());
+    }
+    let starts = game_board.card_bounds(&moving);
+
+    let transfer_stack = origin.split_to_new_on(&selected.widget_name());
+    if to_stack.drop_stack(&transfer_stack) {
+        game_board.animate_cards(starts);
+        true
+    } else {
+        origin.merge_stack(&transfer_stack);
+        false
+    }
+}
+
+fn focus_stack(stack: &CardStack) {
+    match stack.last_card() {
+        Some(card) => card.grab_focus(),
+        None => stack.grab_focus(),
+    };
+}
+
+// Up and down go through the cards of a fanned stack before leaving it,
+// otherwise focus goes to the nearest stack in that direction
+fn move_focus(from: &CardStack, card: Option<Card>, direction: gtk::DirectionType) {
+    if let Some(card) = card {
+        let sibling = match direction {
+            gtk::DirectionType::Up => card.prev_sibling(),
+            gtk::DirectionType::Down => card.next_sibling(),
+            _ => None,
+        };
+        if let Some(sibling) = sibling.and_downcast::<Card>() {
+            sibling.grab_focus();
+            return;
+        }
+    }
+
+    let game_board = from.game_board();
+    // Stacks are compared by the middle of their top edge, so fanned stacks
+    // line up with the ones above them
+    let anchor = |stack: &CardStack| {
+        let bounds = stack.compute_bounds(&game_board)?;
+        Some((bounds.x() + bounds.width() / 2.0, bounds.y()))
+    };
+    let Some((x, y)) = anchor(from) else {
+        return;
+    };
+    let mut nearest: Option<(f32, CardStack)> = None;
+    for stack in game_board.stacks() {
+        if &stack == from || !stack.is_visible() {
+            continue;
+        }
+        let Some((stack_x, stack_y)) = anchor(&stack) else {
+            continue;
+        };
+        let (along, across) = match direction {
+            gtk::DirectionType::Left => (x - stack_x, stack_y - y),
+            gtk::DirectionType::Right => (stack_x - x, stack_y - y),
+            gtk::DirectionType::Up => (y - stack_y, stack_x - x),
+            _ => (stack_y - y, stack_x - x),
+        };
+        if along <= 0.0 {
+            continue;
+        }
+        // Stacks out of line count for more than ones further along
+        let distance = along + across.abs() * 2.0;
+        if nearest.as_ref().is_none_or(|(best, _)| distance < *best) {
+            nearest = Some((distance, stack));
+        }
+    }
+    if let Some((_, stack)) = nearest {
+        focus_stack(&stack);
+    }
+}
@@ -18,13 +18,21 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
+mod analysis;
 mod application;
+mod automation;
 mod card;
 mod card_stack;
+mod celebration;
+mod clicks;
 mod config;
 mod game_board;
 mod games;
+mod keyboard;
+mod player;
+mod preferences;
 mod renderer;
+mod replay;
 mod runtime;
 mod window;
 
@@ -51,7 +59,7 @@ fn main() -> glib::ExitCode {
     // Create a new GtkApplication. The application manages our main loop,
     // application windows, integration with the window manager/compositor, and
     // desktop features such as file opening and single-instance applications.
-    let app = SolitaireApplication::new(APP_ID, &gio::ApplicationFlags::empty());
+    let app = SolitaireApplication::new(APP_ID, &gio::ApplicationFlags::HANDLES_COMMAND_LINE);
 
     // Run the application. This function will block until the application
     // exits. Upon return, we have our exit code to return to the shell. (This
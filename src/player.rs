@@ -0,0 +1,236 @@
+/* player.rs
+ *
+ * Copyright 2026 Will Warner
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use crate::{game_board::GameBoard, runtime};
+use gettextrs::gettext;
+use gtk::glib;
+use gtk::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+// How long each move of the solution is shown at normal speed
+const MOVE_DELAY: Duration = Duration::from_millis(300);
+// Replays keep the pace they were played at, but don't wait this long for a move
+const MAX_REPLAY_DELAY: Duration = Duration::from_secs(2);
+/// The speeds that can be chosen, in the order they are listed
+pub const SPEEDS: [f64; 4] = [0.5, 1.0, 2.0, 4.0];
+
+/// Plays the solver's solution one move at a time. Every move is an ordinary
+/// history entry, so the player can be paused, stepped back with undo and
+/// left at any point to play on by hand.
+///
+/// A replay is played the same way, except that its moves come from the
+/// recording and the board can't be played on until it is closed.
+pub struct Player {
+    open: Cell<bool>,
+    playing: Cell<bool>,
+    speed: Cell<f64>,
+    source: RefCell<Option<glib::SourceId>>,
+    replay: RefCell<Option<Vec<runtime::Transaction>>>,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            open: Cell::new(false),
+            playing: Cell::new(false),
+            speed: Cell::new(1.0),
+            source: RefCell::new(None),
+            replay: RefCell::new(None),
+        }
+    }
+}
+
+/// Shows the solution controls and starts playing from the current position
+pub fn open(game_board: &GameBoard) {
+    let Some(window) = game_board.window() else {
+        return;
+    };
+    if is_replay(game_board) {
+        play(game_board);
+        return;
+    }
+    if runtime::get_hint(game_board).is_none() {
+        window.show_toast(&gettext("No solution available"));
+        return;
+    }
+    game_board.player().open.set(true);
+    window.set_solution_controls(true, true);
+    play(game_board);
+}
+
+/// Shows the controls to play back the recorded moves from the current
+/// position, which must be the one the recording starts from
+pub fn open_replay(game_board: &GameBoard, steps: Vec<runtime::Transaction>) {
+    let Some(window) = game_board.window() else {
+        return;
+    };
+    close(game_board);
+    let player = game_board.player();
+    player.replay.replace(Some(steps));
+    player.open.set(true);
+    // Held until the replay is closed, so it can only be watched
+    game_board.block_input();
+    window.set_solution_controls(true, false);
+    play(game_board);
+}
+
+/// Stops playing and hides the controls, handing the game back to the player
+pub fn close(game_board: &GameBoard) {
+    pause(game_board);
+    let player = game_board.player();
+    if !player.open.replace(false) {
+        return;
+    }
+    if player.replay.take().is_some() {
+        game_board.unblock_input();
+    }
+    if let Some(window) = game_board.window() {
+        window.set_solution_controls(false, true);
+    }
+}
+
+pub fn toggle(game_board: &GameBoard) {
+    if game_board.player().playing.get() {
+        pause(game_board);
+    } else {
+        play(game_board);
+    }
+}
+
+pub fn play(game_board: &GameBoard) {
+    if game_board.player().playing.replace(true) {
+        return;
+    }
+    // Cards can't be moved by hand while the solution plays
+    game_board.block_input();
+    update_controls(game_board);
+    schedule(game_board);
+}
+
+pub fn pause(game_board: &GameBoard) {
+    let player = game_board.player();
+    if !player.playing.replace(false) {
+        return;
+    }
+    if let Some(source) = player.source.take() {
+        source.remove();
+    }
+    game_board.unblock_input();
+    update_controls(game_board);
+}
+
+/// Makes the next move of the solution
+pub fn step_forward(game_board: &GameBoard) {
+    pause(game_board);
+    if runtime::is_busy(game_board) || !step(game_board) {
+        game_board.error_bell();
+    }
+}
+
+/// Takes back the last move, which puts it back in the solution
+pub fn step_back(game_board: &GameBoard) {
+    pause(game_board);
+    if runtime::is_busy(game_board) {
+        return;
+    }
+    runtime::undo_last_move(game_board);
+    if let Some(window) = game_board.window() {
+        runtime::update_redo_actions(&window);
+    }
+}
+
+pub fn set_speed(game_board: &GameBoard, speed: f64) {
+    game_board.player().speed.set(speed);
+}
+
+pub fn is_replay(game_board: &GameBoard) -> bool {
+    game_board.player().replay.borrow().is_some()
+}
+
+fn step(game_board: &GameBoard) -> bool {
+    if is_replay(game_board) {
+        let (n_moves, _) = runtime::get_timeline(game_board);
+        let next = game_board
+            .player()
+            .replay
+            .borrow()
+            .as_ref()
+            .and_then(|steps| steps.get(n_moves).cloned());
+        let Some(transaction) = next else {
+            return false;
+        };
+        runtime::replay_transaction(game_board, transaction);
+        return true;
+    }
+    let Some(mut move_) = runtime::get_hint(game_board) else {
+        return false;
+    };
+    runtime::play_move(game_board, &mut move_);
+    true
+}
+
+fn schedule(game_board: &GameBoard) {
+    let player = game_board.player();
+    let delay = next_delay(game_board).div_f64(player.speed.get());
+    let source = glib::timeout_add_local_once(
+        delay,
+        glib::clone!(
+            #[weak]
+            game_board,
+            move || {
+                game_board.player().source.take();
+                if !game_board.player().playing.get() {
+                    return;
+                }
+                // Wait for autoplay and the solver to catch up
+                if runtime::is_busy(&game_board) || step(&game_board) {
+                    schedule(&game_board);
+                } else {
+                    pause(&game_board);
+                }
+            }
+        ),
+    );
+    player.source.replace(Some(source));
+}
+
+// How long to show the position before the next move
+fn next_delay(game_board: &GameBoard) -> Duration {
+    let min_delay = runtime::step_delay(game_board, MOVE_DELAY);
+    let (n_moves, _) = runtime::get_timeline(game_board);
+    let replay = game_board.player().replay.borrow();
+    let Some(next) = replay.as_ref().and_then(|steps| steps.get(n_moves)) else {
+        return min_delay;
+    };
+    let last = n_moves
+        .checked_sub(1)
+        .and_then(|i| replay.as_ref()?.get(i))
+        .map_or(Duration::ZERO, |last| last.time);
+    next.time
+        .saturating_sub(last)
+        .clamp(min_delay, MAX_REPLAY_DELAY.max(min_delay))
+}
+
+fn update_controls(game_board: &GameBoard) {
+    if let Some(window) = game_board.window() {
+        window.set_solution_playing(game_board.player().playing.get());
+    }
+}
@@ -0,0 +1,347 @@
+/* preferences.rs
+ *
+ * Copyright 2026 Will Warner
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use crate::{games, renderer};
+use adw::prelude::*;
+use gettextrs::gettext;
+use gtk::{gdk, gio, glib};
+
+// The values of the click-action setting, in the order they are listed
+const CLICK_ACTIONS: [&str; 3] = ["none", "pick-up", "smart-move"];
+
+// The values of the table-background setting, in the order they are listed
+const TABLE_BACKGROUNDS: [&str; 4] = ["none", "color", "felt", "image"];
+
+pub fn preferences_dialog() -> adw::PreferencesDialog {
+    let dialog = adw::PreferencesDialog::new();
+    dialog.add(&gameplay_page());
+    dialog.add(&appearance_page());
+    // Only games with rule variants have a group on the rules page
+    if games::GAMES.iter().any(|game| !game.options.is_empty()) {
+        dialog.add(&rules_page());
+    }
+    dialog
+}
+
+fn gameplay_page() -> adw::PreferencesPage {
+    let settings = gio::Settings::new(crate::APP_ID);
+    let page = adw::PreferencesPage::builder()
+        .title(gettext("Gameplay"))
+        .icon_name("input-gaming-symbolic")
+        .build();
+    let group = adw::PreferencesGroup::new();
+    let autoplay_row = adw::SwitchRow::builder()
+        .title(gettext("Autoplay"))
+        .subtitle(gettext("Move cards to the foundations when it is safe"))
+        .build();
+    settings.bind("autoplay", &autoplay_row, "active").build();
+    group.add(&autoplay_row);
+    let auto_finish_row = adw::SwitchRow::builder()
+        .title(gettext("Finish Automatically"))
+        .subtitle(gettext("Play the remaining cards once the game is won"))
+        .build();
+    settings
+        .bind("auto-finish", &auto_finish_row, "active")
+        .build();
+    group.add(&auto_finish_row);
+    let no_moves_row = adw::SwitchRow::builder()
+        .title(gettext("Tell When Stuck"))
+        .subtitle(gettext("Show a banner when no moves are left"))
+        .build();
+    settings
+        .bind("no-moves-banner", &no_moves_row, "active")
+        .build();
+    group.add(&no_moves_row);
+    let click_actions = gtk::StringList::new(&[
+        gettext("Nothing").as_str(),
+        gettext("Pick Up Cards").as_str(),
+        gettext("Move to the Best Place").as_str(),
+    ]);
+    let click_row = adw::ComboRow::builder()
+        .title(gettext("Clicking a Card"))
+        .subtitle(gettext(
+            "Picked up cards are put down by clicking another stack",
+        ))
+        .model(&click_actions)
+        .build();
+    settings
+        .bind("click-action", &click_row, "selected")
+        .mapping(|variant, _| {
+            let action = variant.str()?;
+            let selected = CLICK_ACTIONS.iter().position(|a| *a == action)?;
+            Some((selected as u32).to_value())
+        })
+        .set_mapping(|value, _| {
+            let selected = value.get::<u32>().ok()? as usize;
+            Some(CLICK_ACTIONS.get(selected)?.to_variant())
+        })
+        .build();
+    group.add(&click_row);
+    page.add(&group);
+
+    let animations_group = adw::PreferencesGroup::builder()
+        .title(gettext("Animations"))
+        .build();
+    let reduced_motion_row = adw::SwitchRow::builder()
+        .title(gettext("Reduce Motion"))
+        .subtitle(gettext("Move cards instantly"))
+        .build();
+    settings
+        .bind("reduced-motion", &reduced_motion_row, "active")
+        .build();
+    animations_group.add(&reduced_motion_row);
+    let duration_row = adw::SpinRow::builder()
+        .title(gettext("Animation Duration"))
+        .subtitle(gettext("In milliseconds"))
+        .adjustment(&gtk::Adjustment::new(0.0, 0.0, 1000.0, 50.0, 100.0, 0.0))
+        .build();
+    settings
+        .bind("animation-duration", &duration_row, "value")
+        .no_sensitivity()
+        .build();
+    settings
+        .bind("reduced-motion", &duration_row, "sensitive")
+        .invert_boolean()
+        .get_only()
+        .build();
+    animations_group.add(&duration_row);
+    page.add(&animations_group);
+    page
+}
+
+fn appearance_page() -> adw::PreferencesPage {
+    let settings = gio::Settings::new(crate::APP_ID);
+    let page = adw::PreferencesPage::builder()
+        .title(gettext("Appearance"))
+        .icon_name("applications-graphics-symbolic")
+        .build();
+
+    let cards_group = adw::PreferencesGroup::builder()
+        .title(gettext("Cards"))
+        .build();
+    let theme_names = renderer::theme_names();
+    let back_names = gtk::StringList::new(&[gettext("Same as Cards").as_str()]);
+    for theme_name in &theme_names {
+        back_names.append(theme_name);
+    }
+    let back_row = adw::ComboRow::builder()
+        .title(gettext("Card Back"))
+        .model(&back_names)
+        .build();
+    let back_themes = theme_names.clone();
+    settings
+        .bind("card-back", &back_row, "selected")
+        .mapping(move |variant, _| {
+            let theme_name = variant.str()?;
+            let selected = theme_names
+                .iter()
+                .position(|name| name == theme_name)
+                .map_or(0, |i| i + 1);
+            Some((selected as u32).to_value())
+        })
+        .set_mapping(move |value, _| {
+            let selected = value.get::<u32>().ok()? as usize;
+            let theme_name = match selected {
+                0 => "",
+                selected => back_themes.get(selected - 1)?,
+            };
+            Some(theme_name.to_variant())
+        })
+        .build();
+    cards_group.add(&back_row);
+    cards_group.add(&image_row(
+        &settings,
+        "card-back-image",
+        &gettext("Card Back Image"),
+    ));
+    let four_color_row = adw::SwitchRow::builder()
+        .title(gettext("Four-Color Suits"))
+        .subtitle(gettext("Show diamonds in blue and clubs in green"))
+        .build();
+    settings
+        .bind("four-color-suits", &four_color_row, "active")
+        .build();
+    cards_group.add(&four_color_row);
+    let large_indices_row = adw::SwitchRow::builder()
+        .title(gettext("Large Indices"))
+        .subtitle(gettext(
+            "Show big, high contrast ranks and suits in the corners",
+        ))
+        .build();
+    settings
+        .bind("large-indices", &large_indices_row, "active")
+        .build();
+    cards_group.add(&large_indices_row);
+    page.add(&cards_group);
+
+    let table_group = adw::PreferencesGroup::builder()
+        .title(gettext("Table"))
+        .build();
+    let backgrounds = gtk::StringList::new(&[
+        gettext("Default").as_str(),
+        gettext("Solid Color").as_str(),
+        gettext("Felt").as_str(),
+        gettext("Image").as_str(),
+    ]);
+    let background_row = adw::ComboRow::builder()
+        .title(gettext("Background"))
+        .model(&backgrounds)
+        .build();
+    settings
+        .bind("table-background", &background_row, "selected")
+        .mapping(|variant, _| {
+            let background = variant.str()?;
+            let selected = TABLE_BACKGROUNDS.iter().position(|b| *b == background)?;
+            Some((selected as u32).to_value())
+        })
+        .set_mapping(|value, _| {
+            let selected = value.get::<u32>().ok()? as usize;
+            Some(TABLE_BACKGROUNDS.get(selected)?.to_variant())
+        })
+        .build();
+    table_group.add(&background_row);
+
+    let color_button = gtk::ColorDialogButton::builder()
+        .dialog(&gtk::ColorDialog::builder().with_alpha(false).build())
+        .valign(gtk::Align::Center)
+        .build();
+    settings
+        .bind("table-color", &color_button, "rgba")
+        .mapping(|variant, _| Some(gdk::RGBA::parse(variant.str()?).ok()?.to_value()))
+        .set_mapping(|value, _| Some(value.get::<gdk::RGBA>().ok()?.to_str().to_variant()))
+        .build();
+    let color_row = adw::ActionRow::builder()
+        .title(gettext("Color"))
+        .activatable_widget(&color_button)
+        .build();
+    color_row.add_suffix(&color_button);
+    settings
+        .bind("table-background", &color_row, "sensitive")
+        .get_only()
+        .mapping(|variant, _| Some(matches!(variant.str(), Some("color" | "felt")).to_value()))
+        .build();
+    table_group.add(&color_row);
+
+    let table_image_row = image_row(&settings, "table-image", &gettext("Image"));
+    settings
+        .bind("table-background", &table_image_row, "sensitive")
+        .get_only()
+        .mapping(|variant, _| Some((variant.str() == Some("image")).to_value()))
+        .build();
+    table_group.add(&table_image_row);
+    page.add(&table_group);
+    page
+}
+
+// A row showing the image an URI setting points to, with buttons to choose another one or none
+fn image_row(settings: &gio::Settings, key: &'static str, title: &str) -> adw::ActionRow {
+    let row = adw::ActionRow::builder().title(title).build();
+    settings
+        .bind(key, &row, "subtitle")
+        .get_only()
+        .mapping(|variant, _| {
+            let uri = variant.str()?;
+            let name = if uri.is_empty() {
+                gettext("None")
+            } else {
+                gio::File::for_uri(uri)
+                    .basename()
+                    .map_or(uri.to_string(), |name| name.display().to_string())
+            };
+            Some(name.to_value())
+        })
+        .build();
+
+    let clear_button = gtk::Button::builder()
+        .icon_name("edit-clear-symbolic")
+        .tooltip_text(gettext("Remove Image"))
+        .valign(gtk::Align::Center)
+        .css_classes(["flat"])
+        .build();
+    settings
+        .bind(key, &clear_button, "sensitive")
+        .get_only()
+        .mapping(|variant, _| Some(variant.str().is_some_and(|uri| !uri.is_empty()).to_value()))
+        .build();
+    clear_button.connect_clicked(glib::clone!(
+        #[strong]
+        settings,
+        move |_| {
+            settings.set_string(key, "").ok();
+        }
+    ));
+
+    let choose_button = gtk::Button::builder()
+        .icon_name("document-open-symbolic")
+        .tooltip_text(gettext("Choose Image"))
+        .valign(gtk::Align::Center)
+        .css_classes(["flat"])
+        .build();
+    choose_button.connect_clicked(glib::clone!(
+        #[strong]
+        settings,
+        move |button| {
+            let filter = gtk::FileFilter::new();
+            filter.set_name(Some(&gettext("Images")));
+            filter.add_pixbuf_formats();
+            let dialog = gtk::FileDialog::builder()
+                .title(gettext("Choose Image"))
+                .default_filter(&filter)
+                .build();
+            let window = button.root().and_downcast::<gtk::Window>();
+            let settings = settings.clone();
+            dialog.open(window.as_ref(), None::<&gio::Cancellable>, move |file| {
+                if let Ok(file) = file {
+                    settings.set_string(key, &file.uri()).ok();
+                }
+            });
+        }
+    ));
+    row.add_suffix(&clear_button);
+    row.add_suffix(&choose_button);
+    row
+}
+
+fn rules_page() -> adw::PreferencesPage {
+    let page = adw::PreferencesPage::builder()
+        .title(gettext("Rules"))
+        .icon_name("view-list-symbolic")
+        .build();
+    for game in games::GAMES {
+        if game.options.is_empty() {
+            continue;
+        }
+        let group = adw::PreferencesGroup::builder()
+            .title((game.name)())
+            .description(gettext("Changes apply to the next game"))
+            .build();
+        let options = game.load_options();
+        for option in game.options {
+            let row = adw::SwitchRow::builder()
+                .title((option.title)())
+                .active(options.get(option.id))
+                .build();
+            row.connect_active_notify(move |row| game.save_option(option.id, row.is_active()));
+            group.add(&row);
+        }
+        page.add(&group);
+    }
+    page
+}
@@ -20,17 +20,64 @@
 
 use crate::{card::Card, games};
 use gtk::gdk::*;
+use gtk::prelude::*;
 
 thread_local! {
-    pub static BACK_TEXTURE: std::cell::RefCell<Option<MemoryTexture>> = std::cell::RefCell::new(None);
-    pub static ASPECT:std::cell::Cell<f32> = std::cell::Cell::new(0.0);
-    pub static ACTIVE_THEME:std::cell::RefCell<String> = std::cell::RefCell::new(String::new());
+    // Poker cards until a theme is loaded
+    pub static ASPECT:std::cell::Cell<f32> = std::cell::Cell::new(1.4);
 }
 
-pub const THEME_NAMES: [&str; 3] = ["anglo_poker", "minimum", "minimum_dark"];
+pub const BUNDLED_THEMES: [&str; 3] = ["anglo_poker", "minimum", "minimum_dark"];
+const FALLBACK_THEME: &str = "anglo_poker";
+// Card themes are sheets of 13 ranks by 5 rows: the four suits, then jokers and the back
+const SHEET_COLUMNS: f64 = 13.0;
+const SHEET_ROWS: f64 = 5.0;
+
+// The directories searched for user installed themes, most important first
+fn theme_dirs() -> Vec<std::path::PathBuf> {
+    std::iter::once(glib::user_data_dir())
+        .chain(glib::system_data_dirs())
+        .map(|dir| dir.join("solitaire").join("card_themes"))
+        .collect()
+}
+
+fn find_theme_file(theme_name: &str) -> Option<std::path::PathBuf> {
+    theme_dirs().into_iter().find_map(|dir| {
+        ["svg", "svgz"]
+            .iter()
+            .map(|extension| dir.join(format!("{theme_name}.{extension}")))
+            .find(|path| path.is_file())
+    })
+}
+
+/// The bundled themes followed by the ones installed in the data directories
+pub fn theme_names() -> Vec<String> {
+    let mut installed = Vec::new();
+    for dir in theme_dirs() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_svg = path
+                .extension()
+                .is_some_and(|extension| extension == "svg" || extension == "svgz");
+            if let (true, Some(name)) = (is_svg, path.file_stem().and_then(|s| s.to_str())) {
+                installed.push(name.to_string());
+            }
+        }
+    }
+    installed.sort();
+    let mut names: Vec<String> = BUNDLED_THEMES.iter().map(|name| name.to_string()).collect();
+    for name in installed {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
 
 pub fn get_requested_theme() -> String {
-    use gtk::prelude::*;
     let settings = gtk::gio::Settings::new(crate::APP_ID);
     settings.get::<String>("theme")
 }
@@ -41,19 +88,24 @@ pub fn draw_theme_preview(
     renderer: &rsvg::CairoRenderer,
     picture: &gtk::Picture,
 ) {
-    let width = card_theme.theme_width as i32;
-    let height = card_theme.theme_height as i32;
+    let width = card_theme.sheet.theme_width as i32;
+    let height = card_theme.sheet.theme_height as i32;
     let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)
         .expect("Couldn't create surface");
 
     let cr = cairo::Context::new(&surface).expect("Couldn't create cairo context");
     // Render a single SVG layer, marked by a <g>
-    renderer
-        .render_document(
-            &cr,
-            &cairo::Rectangle::new(0f64, 0f64, card_theme.theme_width, card_theme.theme_height),
-        )
-        .expect(&format!("Failed to render layer {name}"));
+    if let Err(error) = renderer.render_document(
+        &cr,
+        &cairo::Rectangle::new(
+            0f64,
+            0f64,
+            card_theme.sheet.theme_width,
+            card_theme.sheet.theme_height,
+        ),
+    ) {
+        glib::g_warning!("solitaire", "Failed to render theme {name}: {error}");
+    }
 
     drop(cr);
     let stride = surface.stride() as usize;
@@ -72,104 +124,640 @@ pub fn draw_theme_preview(
     picture.set_paintable(Some(&texture));
 }
 
+#[derive(Clone)]
 pub struct CardTheme {
-    pub handle: rsvg::SvgHandle,
+    pub name: String,
+    svg: glib::Bytes,
+    sheet: Sheet,
+    // Changes whenever the theme file does, to tell stale cached textures apart
+    stamp: String,
+}
+
+#[derive(Clone, Copy)]
+struct Sheet {
     card_width: i32,
     card_height: i32,
     theme_width: f64,
     theme_height: f64,
 }
 
-pub fn draw_card(
+impl CardTheme {
+    pub fn handle(&self) -> Result<rsvg::SvgHandle, String> {
+        rsvg::Loader::new()
+            .read_stream(
+                &gio::MemoryInputStream::from_bytes(&self.svg),
+                None::<&gio::File>,
+                None::<&gio::Cancellable>,
+            )
+            .map_err(|error| error.to_string())
+    }
+
+    /// The height of a card divided by its width
+    pub fn aspect(&self) -> f32 {
+        self.sheet.card_height as f32 / self.sheet.card_width as f32
+    }
+
+    fn card_height(&self, width: i32) -> i32 {
+        (width as f64 * self.sheet.card_height as f64 / self.sheet.card_width as f64).round() as i32
+    }
+}
+
+/// What a worker loading a theme reports back
+pub enum ThemeProgress {
+    /// The fraction of the cards rendered so far
+    Rendering(f64),
+    /// The theme that was loaded, which is the fallback theme if the requested
+    /// one couldn't be, with the faces in deck order followed by the back
+    Done(CardTheme, Vec<Texture>),
+}
+
+/// How the cards look beyond the theme itself, read from the settings
+#[derive(Clone, PartialEq)]
+pub struct RenderOptions {
+    four_color: bool,
+    large_indices: bool,
+    back_theme: String,
+    back_image: String,
+}
+
+impl RenderOptions {
+    pub fn from_settings() -> Self {
+        let settings = gio::Settings::new(crate::APP_ID);
+        Self {
+            four_color: settings.boolean("four-color-suits"),
+            large_indices: settings.boolean("large-indices"),
+            back_theme: settings.string("card-back").to_string(),
+            back_image: settings.string("card-back-image").to_string(),
+        }
+    }
+
+    // Names the cached cards rendered with these options
+    fn cache_key(&self, width: i32) -> String {
+        let mut key = width.to_string();
+        if self.four_color {
+            key.push_str("-four-color");
+        }
+        if self.large_indices {
+            key.push_str("-large-indices");
+        }
+        key
+    }
+}
+
+// Every layer of a theme with its place on the sheet, the faces in deck order followed by the back
+fn card_layers() -> impl Iterator<Item = (String, i32, i32)> {
+    (0..52)
+        .map(|i| {
+            let name = format!("{}_{}", games::SUITES[i / 13], games::RANKS[i % 13]);
+            (name, (i % 13) as i32, (i / 13) as i32)
+        })
+        .chain(std::iter::once(("back".to_string(), 2, 4)))
+}
+
+// Renders one card at the given size into the pixels of an ARGB32 surface and their stride
+fn rasterize(
     name: &str,
     renderer: &rsvg::CairoRenderer,
-    card_theme: &CardTheme,
-    card_x: i32,
-    card_y: i32,
-) -> MemoryTexture {
-    let surface = cairo::ImageSurface::create(
-        cairo::Format::ARgb32,
-        card_theme.card_width,
-        card_theme.card_height,
-    )
-    .expect("Couldn't create surface");
+    sheet: Sheet,
+    (card_x, card_y): (i32, i32),
+    width: i32,
+    height: i32,
+    options: &RenderOptions,
+) -> (glib::Bytes, usize) {
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)
+        .expect("Couldn't create surface");
 
     let cr = cairo::Context::new(&surface).expect("Couldn't create cairo context");
+    cr.scale(
+        width as f64 / sheet.card_width as f64,
+        height as f64 / sheet.card_height as f64,
+    );
     // Render a single SVG layer, marked by a <g>
-    renderer
-        .render_layer(
+    if let Err(error) = renderer.render_layer(
+        &cr,
+        Some(&format!("#{name}")),
+        &cairo::Rectangle::new(
+            (-sheet.card_width * card_x) as f64,
+            (-sheet.card_height * card_y) as f64,
+            sheet.theme_width,
+            sheet.theme_height,
+        ),
+    ) {
+        glib::g_warning!("solitaire", "Failed to render layer {name}: {error}");
+    }
+    // The rows of the sheet are the suits, apart from the last one
+    if options.large_indices && card_y < 4 {
+        cr.identity_matrix();
+        draw_large_index(
             &cr,
-            Some(&format!("#{name}")),
-            &cairo::Rectangle::new(
-                (-card_theme.card_width * card_x) as f64,
-                (-card_theme.card_height * card_y) as f64,
-                card_theme.theme_width,
-                card_theme.theme_height,
-            ),
-        )
-        .expect(&format!("Failed to render layer {name}"));
+            card_x as usize,
+            card_y as usize,
+            width,
+            height,
+            options,
+        );
+    }
 
     drop(cr);
     let stride = surface.stride() as usize;
-    let data = surface
+    let mut data = surface
         .take_data()
         .expect("Failed to get data from surface");
-    // Create a texture from the surface
-    let bytes = glib::Bytes::from(&data[..]);
+    if options.four_color && card_y < 4 {
+        recolor_suit(&mut data, card_y as usize);
+    }
+    (glib::Bytes::from(&data[..]), stride)
+}
+
+// Covers both corners of a face with a big rank and suit in strong colors, for
+// players who can't make out the theme's own indices
+fn draw_large_index(
+    cr: &cairo::Context,
+    rank: usize,
+    suit: usize,
+    width: i32,
+    height: i32,
+    options: &RenderOptions,
+) {
+    const LABELS: [&str; 13] = [
+        "A", "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K",
+    ];
+    const SYMBOLS: [&str; 4] = ["\u{2663}", "\u{2666}", "\u{2665}", "\u{2660}"];
+    let (red, green, blue) = match (games::SUITES[suit], options.four_color) {
+        ("diamond", true) => (0.0, 0.2, 0.8),
+        ("club", true) => (0.0, 0.45, 0.0),
+        ("diamond" | "heart", _) => (0.8, 0.0, 0.0),
+        _ => (0.0, 0.0, 0.0),
+    };
+    let (width, height) = (width as f64, height as f64);
+    let (x, y) = (width * 0.03, height * 0.02);
+    let (box_width, box_height) = (width * 0.3, height * 0.32);
+    cr.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Bold);
+    for corner in 0..2 {
+        cr.save().ok();
+        if corner == 1 {
+            // Upside down in the opposite corner, like the theme's own
+            cr.translate(width, height);
+            cr.rotate(std::f64::consts::PI);
+        }
+        cr.rectangle(x, y, box_width, box_height);
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        cr.fill().ok();
+        cr.set_source_rgb(red, green, blue);
+        for (text, font_size, center) in [
+            (LABELS[rank], box_height * 0.5, 0.3),
+            (SYMBOLS[suit], box_height * 0.45, 0.75),
+        ] {
+            cr.set_font_size(font_size);
+            let Ok(mut extents) = cr.text_extents(text) else {
+                continue;
+            };
+            // Ten is wider than the other ranks
+            if extents.width() > box_width * 0.9 {
+                cr.set_font_size(font_size * box_width * 0.9 / extents.width());
+                let Ok(fitted) = cr.text_extents(text) else {
+                    continue;
+                };
+                extents = fitted;
+            }
+            cr.move_to(
+                x + (box_width - extents.width()) / 2.0 - extents.x_bearing(),
+                y + box_height * center - extents.height() / 2.0 - extents.y_bearing(),
+            );
+            cr.show_text(text).ok();
+        }
+        cr.restore().ok();
+    }
+}
+
+// Four color decks show diamonds in blue and clubs in green. Any theme can be
+// recolored this way, as red and black are all that tell the suits apart.
+fn recolor_suit(data: &mut [u8], suit: usize) {
+    for pixel in data.chunks_exact_mut(4) {
+        // ARGB32 is stored as premultiplied BGRA
+        let alpha = pixel[3] as u32;
+        if alpha == 0 {
+            continue;
+        }
+        let unpremultiply = |c: u8| (c as u32 * 255 / alpha).min(255);
+        let (b, g, r) = (
+            unpremultiply(pixel[0]),
+            unpremultiply(pixel[1]),
+            unpremultiply(pixel[2]),
+        );
+        let (b, g, r) = match games::SUITES[suit] {
+            // Red turns blue, keeping its shade
+            "diamond" if r > 96 && r > 2 * g && r > 2 * b => (r, g, b),
+            // Black and dark grey turn dark green
+            "club" if r.max(g).max(b) < 112 && r.max(g).max(b) - r.min(g).min(b) < 48 => {
+                let value = r.max(g).max(b);
+                (value / 3, 112 + value, value / 3)
+            }
+            _ => continue,
+        };
+        let premultiply = |c: u32| (c * alpha / 255) as u8;
+        pixel[0] = premultiply(b);
+        pixel[1] = premultiply(g);
+        pixel[2] = premultiply(r);
+    }
+}
+
+// Draws an image over the whole card, cropping what doesn't fit and rounding the corners
+fn rasterize_image(texture: &Texture, width: i32, height: i32) -> Option<(glib::Bytes, usize)> {
+    let (image_width, image_height) = (texture.width(), texture.height());
+    let image_stride = image_width as usize * 4;
+    let mut image_data = vec![0; image_stride * image_height as usize];
+    texture.download(&mut image_data, image_stride);
+    let image = cairo::ImageSurface::create_for_data(
+        image_data,
+        cairo::Format::ARgb32,
+        image_width,
+        image_height,
+        image_stride as i32,
+    )
+    .ok()?;
+
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height).ok()?;
+    let cr = cairo::Context::new(&surface).ok()?;
+    let radius = width as f64 * 0.06;
+    let (w, h) = (width as f64, height as f64);
+    cr.new_sub_path();
+    cr.arc(w - radius, radius, radius, -90f64.to_radians(), 0.0);
+    cr.arc(w - radius, h - radius, radius, 0.0, 90f64.to_radians());
+    cr.arc(
+        radius,
+        h - radius,
+        radius,
+        90f64.to_radians(),
+        180f64.to_radians(),
+    );
+    cr.arc(
+        radius,
+        radius,
+        radius,
+        180f64.to_radians(),
+        270f64.to_radians(),
+    );
+    cr.close_path();
+    cr.clip();
+    let scale = (w / image_width as f64).max(h / image_height as f64);
+    cr.translate(
+        (w - image_width as f64 * scale) / 2.0,
+        (h - image_height as f64 * scale) / 2.0,
+    );
+    cr.scale(scale, scale);
+    cr.set_source_surface(&image, 0.0, 0.0).ok()?;
+    cr.paint().ok()?;
+    drop(cr);
+
+    let stride = surface.stride() as usize;
+    let data = surface.take_data().ok()?;
+    Some((glib::Bytes::from(&data[..]), stride))
+}
+
+// The back chosen in the settings, if it isn't the theme's own
+fn render_back(
+    card_theme: &CardTheme,
+    options: &RenderOptions,
+    width: i32,
+    height: i32,
+) -> Option<Texture> {
+    if !options.back_image.is_empty() {
+        let image = Texture::from_file(&gio::File::for_uri(&options.back_image))
+            .map_err(|error| {
+                glib::g_warning!("solitaire", "Couldn't load the card back image: {error}")
+            })
+            .ok()?;
+        let back = rasterize_image(&image, width, height)?;
+        return Some(to_texture(width, height, back));
+    }
+    if options.back_theme.is_empty() || options.back_theme == card_theme.name {
+        return None;
+    }
+    let back_theme = load_card_theme(&options.back_theme)
+        .map_err(|error| {
+            glib::g_warning!(
+                "solitaire",
+                "Couldn't load card theme {}: {error}",
+                options.back_theme
+            )
+        })
+        .ok()?;
+    let handle = back_theme.handle().ok()?;
+    let renderer = rsvg::CairoRenderer::new(&handle);
+    let back = rasterize(
+        "back",
+        &renderer,
+        back_theme.sheet,
+        (2, 4),
+        width,
+        height,
+        options,
+    );
+    Some(to_texture(width, height, back))
+}
+
+fn to_texture(width: i32, height: i32, (bytes, stride): (glib::Bytes, usize)) -> Texture {
     MemoryTexture::new(
-        card_theme.card_width,
-        card_theme.card_height,
+        width,
+        height,
         MemoryFormat::B8g8r8a8Premultiplied, // Match ARGB32 surface
         &bytes,
         stride,
     )
+    .upcast()
+}
+
+// Rendered cards are kept in the cache directory, one directory of PNGs per
+// theme and card width
+fn cache_dir(theme_name: &str) -> std::path::PathBuf {
+    glib::user_cache_dir()
+        .join("solitaire")
+        .join("card-textures")
+        .join(theme_name)
+}
+
+fn cached_widths(card_theme: &CardTheme, options: &RenderOptions) -> Vec<i32> {
+    let Ok(entries) = std::fs::read_dir(cache_dir(&card_theme.name)) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            let width = name.split('-').next()?.parse().ok()?;
+            (options.cache_key(width) == name).then_some(width)
+        })
+        .collect()
 }
 
-pub fn set_back_texture(renderer: &rsvg::CairoRenderer, card_theme: &CardTheme) {
-    let texture = draw_card("back", renderer, &card_theme, 2, 4); //TODO: More card sizes
-    BACK_TEXTURE.with(|t| t.borrow_mut().replace(texture));
+fn load_cached_cards(
+    card_theme: &CardTheme,
+    width: i32,
+    options: &RenderOptions,
+) -> Option<Vec<Texture>> {
+    let dir = cache_dir(&card_theme.name).join(options.cache_key(width));
+    (0..53)
+        .map(|i| Texture::from_filename(dir.join(format!("{i}.png"))).ok())
+        .collect()
 }
 
+fn save_cached_cards(
+    card_theme: &CardTheme,
+    width: i32,
+    options: &RenderOptions,
+    textures: &[Texture],
+) {
+    let dir = cache_dir(&card_theme.name).join(options.cache_key(width));
+    if let Err(error) = std::fs::create_dir_all(&dir) {
+        glib::g_warning!("solitaire", "Couldn't create {}: {error}", dir.display());
+        return;
+    }
+    for (i, texture) in textures.iter().enumerate() {
+        if let Err(error) = texture.save_to_png(dir.join(format!("{i}.png"))) {
+            glib::g_warning!("solitaire", "Couldn't cache card textures: {error}");
+            return;
+        }
+    }
+}
+
+// Renders the whole deck, or reads it from the cache. A width of zero picks the
+// largest cached width, or the theme's own size when nothing is cached.
+fn render_deck(
+    card_theme: &CardTheme,
+    width: i32,
+    options: &RenderOptions,
+    progress: impl Fn(f64),
+) -> Vec<Texture> {
+    let width = match width {
+        0 => cached_widths(card_theme, options)
+            .into_iter()
+            .max()
+            .unwrap_or(card_theme.sheet.card_width),
+        width => width,
+    };
+    let height = card_theme.card_height(width);
+    let mut textures = match load_cached_cards(card_theme, width, options) {
+        Some(textures) => textures,
+        None => {
+            let handle = match card_theme.handle() {
+                Ok(handle) => handle,
+                Err(error) => {
+                    glib::g_warning!("solitaire", "Failed to load SVG: {error}");
+                    return Vec::new();
+                }
+            };
+            let renderer = rsvg::CairoRenderer::new(&handle);
+            let textures: Vec<Texture> = card_layers()
+                .enumerate()
+                .map(|(i, (name, x, y))| {
+                    progress(i as f64 / 53.0);
+                    let image = rasterize(
+                        &name,
+                        &renderer,
+                        card_theme.sheet,
+                        (x, y),
+                        width,
+                        height,
+                        options,
+                    );
+                    to_texture(width, height, image)
+                })
+                .collect();
+            save_cached_cards(card_theme, width, options, &textures);
+            textures
+        }
+    };
+    // Other backs are cheap enough to render every time
+    if let Some(back) = render_back(card_theme, options, width, height) {
+        *textures.last_mut().unwrap() = back;
+    }
+    progress(1.0);
+    textures
+}
+
+/// Loads a theme and renders its cards `width` pixels wide on a worker thread.
+/// A width of zero uses whatever size is ready soonest.
+pub fn load_theme(
+    theme_name: String,
+    width: i32,
+    options: RenderOptions,
+) -> async_channel::Receiver<ThemeProgress> {
+    let (sender, receiver) = async_channel::unbounded();
+    std::thread::spawn(move || {
+        let card_theme = get_card_theme(&theme_name);
+        let textures = render_deck(&card_theme, width, &options, |fraction| {
+            sender
+                .send_blocking(ThemeProgress::Rendering(fraction))
+                .ok();
+        });
+        sender
+            .send_blocking(ThemeProgress::Done(card_theme, textures))
+            .ok();
+    });
+    receiver
+}
+
+/// Renders the whole deck `width` pixels wide on a worker thread. The faces
+/// come in deck order, followed by the back.
+pub async fn render_cards(
+    card_theme: &CardTheme,
+    width: i32,
+    options: RenderOptions,
+) -> Option<Vec<Texture>> {
+    let card_theme = card_theme.clone();
+    let (sender, receiver) = async_channel::bounded(1);
+    std::thread::spawn(move || {
+        sender
+            .send_blocking(render_deck(&card_theme, width, &options, |_| {}))
+            .ok();
+    });
+    let textures = receiver.recv().await.ok()?;
+    (textures.len() == 53).then_some(textures)
+}
+
+/// Loads a card theme, falling back to a bundled one if it is missing or broken
 pub fn get_card_theme(theme_name: &str) -> CardTheme {
-    let (card_width, card_height, theme_width, theme_height) = match theme_name {
-        "anglo_poker" => (241, 337, 3133, 1685),
-        "minimum" => (100, 140, 1300, 700),
-        "minimum_dark" => (100, 140, 1300, 700),
-        _ => panic!("Unknown card theme: {}", theme_name),
+    match load_card_theme(theme_name) {
+        Ok(card_theme) => card_theme,
+        Err(error) => {
+            glib::g_warning!(
+                "solitaire",
+                "Couldn't load card theme {theme_name}: {error}"
+            );
+            load_card_theme(FALLBACK_THEME).expect("Failed to load the fallback card theme")
+        }
+    }
+}
+
+fn load_card_theme(theme_name: &str) -> Result<CardTheme, String> {
+    // Installed themes take precedence over bundled ones with the same name
+    let (svg, stamp) = if let Some(path) = find_theme_file(theme_name) {
+        let metadata = std::fs::metadata(&path).map_err(|error| error.to_string())?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_secs());
+        let svg = glib::Bytes::from_owned(std::fs::read(&path).map_err(|error| error.to_string())?);
+        (svg, format!("{modified}-{}", metadata.len()))
+    } else if BUNDLED_THEMES.contains(&theme_name) {
+        let svg = gio::resources_lookup_data(
+            &*format!("/org/gnome/gitlab/wwarner/Solitaire/card_themes/{theme_name}.svg"),
+            gio::ResourceLookupFlags::NONE,
+        )
+        .map_err(|error| error.to_string())?;
+        (svg, crate::config::VERSION.to_string())
+    } else {
+        return Err("no such theme".to_string());
     };
 
+    // Measuring a theme means parsing it, which is slow, so the sheet is cached as well
+    let key_file = glib::KeyFile::new();
+    let metadata_path = cache_dir(theme_name).join("theme.ini");
+    if key_file
+        .load_from_file(&metadata_path, glib::KeyFileFlags::NONE)
+        .is_ok()
+        && key_file
+            .string("Theme", "Stamp")
+            .is_ok_and(|cached_stamp| cached_stamp == stamp)
+    {
+        let sheet = (|| {
+            Some(Sheet {
+                card_width: key_file.integer("Theme", "CardWidth").ok()?,
+                card_height: key_file.integer("Theme", "CardHeight").ok()?,
+                theme_width: key_file.double("Theme", "ThemeWidth").ok()?,
+                theme_height: key_file.double("Theme", "ThemeHeight").ok()?,
+            })
+        })();
+        if let Some(sheet) = sheet {
+            return Ok(CardTheme {
+                name: theme_name.to_string(),
+                svg,
+                sheet,
+                stamp,
+            });
+        }
+    }
+
     glib::g_message!("solitaire", "Loading SVG");
-    let resource = gio::resources_lookup_data(
-        &*format!("/org/gnome/gitlab/wwarner/Solitaire/card_themes/{theme_name}.svg"),
-        gio::ResourceLookupFlags::NONE,
-    )
-    .expect("Failed to load resource data");
-    glib::g_message!("solitaire", "loaded resource data");
-    let handle = rsvg::Loader::new()
-        .read_stream(
-            &gio::MemoryInputStream::from_bytes(&resource),
-            None::<&gio::File>,
-            None::<&gio::Cancellable>,
-        )
-        .expect("Failed to load SVG");
+    let mut card_theme = CardTheme {
+        name: theme_name.to_string(),
+        svg,
+        sheet: Sheet {
+            card_width: 0,
+            card_height: 0,
+            theme_width: 0.0,
+            theme_height: 0.0,
+        },
+        stamp,
+    };
+    let handle = card_theme.handle()?;
     glib::g_message!("solitaire", "Done Loading SVG");
-    CardTheme {
-        handle,
+
+    let layers = games::SUITES
+        .iter()
+        .flat_map(|suite| {
+            games::RANKS
+                .iter()
+                .map(move |rank| format!("#{suite}_{rank}"))
+        })
+        .chain(std::iter::once("#back".to_string()));
+    for layer in layers {
+        if !handle.has_element_with_id(&layer).unwrap_or(false) {
+            return Err(format!("missing layer {layer}"));
+        }
+    }
+
+    // Themes without a size of their own are measured by their view box
+    let renderer = rsvg::CairoRenderer::new(&handle);
+    let (theme_width, theme_height) = renderer
+        .intrinsic_size_in_pixels()
+        .or_else(|| {
+            let view_box = renderer.intrinsic_dimensions().vbox?;
+            Some((view_box.width(), view_box.height()))
+        })
+        .ok_or("the SVG has no size")?;
+    let card_width = (theme_width / SHEET_COLUMNS).round() as i32;
+    let card_height = (theme_height / SHEET_ROWS).round() as i32;
+    if card_width < 1 || card_height < 1 {
+        return Err("the cards have no size".to_string());
+    }
+    card_theme.sheet = Sheet {
         card_width,
         card_height,
-        theme_width: theme_width as f64,
-        theme_height: theme_height as f64,
+        theme_width,
+        theme_height,
+    };
+
+    // The theme changed, so do all of its cached cards
+    let _ = std::fs::remove_dir_all(cache_dir(theme_name));
+    key_file.set_string("Theme", "Stamp", &card_theme.stamp);
+    key_file.set_integer("Theme", "CardWidth", card_width);
+    key_file.set_integer("Theme", "CardHeight", card_height);
+    key_file.set_double("Theme", "ThemeWidth", theme_width);
+    key_file.set_double("Theme", "ThemeHeight", theme_height);
+    let saved = std::fs::create_dir_all(cache_dir(theme_name))
+        .map_err(|error| error.to_string())
+        .and_then(|_| {
+            key_file
+                .save_to_file(&metadata_path)
+                .map_err(|error| error.to_string())
+        });
+    if let Err(error) = saved {
+        glib::g_warning!(
+            "solitaire",
+            "Couldn't cache card theme {theme_name}: {error}"
+        );
     }
+
+    Ok(card_theme)
 }
 
-pub fn create_cards(card_theme: &CardTheme, cards: &mut Vec<Card>) {
-    ASPECT.set(card_theme.card_height as f32 / card_theme.card_width as f32);
-    let renderer = rsvg::CairoRenderer::new(&card_theme.handle);
+/// Creates the deck. The cards have no images until a theme is loaded with `load_theme`.
+pub fn create_cards(cards: &mut Vec<Card>) {
     for i in 0..52 {
         let card_name = format!("{}_{}", games::SUITES[i / 13], games::RANKS[i % 13]);
-        let card = Card::new(&*card_name, i as i32, &renderer, &card_theme);
-        cards.push(card);
+        cards.push(Card::new(&card_name, i as i32));
     }
-    set_back_texture(&renderer, &card_theme);
-    glib::g_message!("solitaire", "Done setting textures");
 }
@@ -0,0 +1,558 @@
+/* replay.rs
+ *
+ * Copyright 2026 Will Warner
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Recorded games that can be shared and played back.
+//!
+//! A replay is a text file with one entry per line. Blank lines and lines
+//! starting with `#` are ignored.
+//!
+//! ```text
+//! solitaire-replay 1
+//! game klondike
+//! stack tableau_0 heart_queen
+//! stack tableau_1 ~club_2 spade_jack
+//! step 1520
+//! move tableau_1 spade_jack tableau_0
+//! flip tableau_1 club_2
+//! score 5
+//! ```
+//!
+//! - `solitaire-replay` comes first, with the version of the format.
+//! - `game` is the id of the game and `option` a rule option it was dealt with.
+//! - `stack` lists the cards a stack was dealt, from the bottom up. Face-down
+//!   cards start with `~`. Every stack of the game is listed.
+//! - `step` starts a history entry, with the milliseconds since the deal it
+//!   was made at. The actions of the entry follow it in the order they
+//!   happened: `move` with the origin, the card moved with the ones on top of
+//!   it, the destination and `flip` if they were turned over and reversed on
+//!   the way, `flip` and `unflip` for a card turned face up or face down, and
+//!   `score` for points won or lost.
+
+use crate::{
+    game_board::GameBoard,
+    games::{self, solver, GameOptions},
+    player,
+    runtime::{self, Action, Move, MoveInstruction, Transaction},
+    window::SolitaireWindow,
+};
+use adw::prelude::*;
+use gettextrs::gettext;
+use gtk::{gio, glib};
+use std::time::Duration;
+
+const MAGIC: &str = "solitaire-replay";
+const VERSION: u32 = 1;
+// Marks a face-down card in a dealt stack
+const FACE_DOWN: char = '~';
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub game_id: String,
+    pub options: GameOptions,
+    /// The solver cards of each stack when the game was dealt
+    pub layout: Vec<(String, Vec<u8>)>,
+    /// The history entries, ready to be made again from the deal
+    pub steps: Vec<Transaction>,
+}
+
+impl Replay {
+    /// Records the moves made on the board so far
+    pub fn record(game_board: &GameBoard) -> Option<Self> {
+        let session = game_board.session();
+        if session.game.borrow().is_none() {
+            return None;
+        }
+        let (stack_names, mut game_state) = runtime::get_solver_state(game_board);
+        let (_positions, history) = runtime::get_positions(game_board);
+
+        // History keeps moves ready to be undone, so take them back one by
+        // one to find out how they were first made
+        let mut steps = Vec::new();
+        for transaction in history.iter().rev() {
+            let mut step = Transaction {
+                time: transaction.time,
+                ..Transaction::default()
+            };
+            for action in transaction.actions.iter().rev() {
+                step.actions
+                    .push(revert(&stack_names, &mut game_state, action).ok()?);
+            }
+            step.actions.reverse();
+            steps.push(step);
+        }
+        steps.reverse();
+
+        Some(Self {
+            game_id: session.game_id.get().to_string(),
+            options: session.options.borrow().clone(),
+            layout: stack_names.into_iter().zip(game_state).collect(),
+            steps,
+        })
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        match lines.next() {
+            Some((_, line)) if line == format!("{MAGIC} {VERSION}") => {}
+            Some((_, line)) if line.starts_with(MAGIC) => {
+                return Err(gettext(
+                    "The replay was made by a newer version of Solitaire",
+                ));
+            }
+            _ => return Err(gettext("The file is not a replay")),
+        }
+
+        let mut replay = Self {
+            game_id: String::new(),
+            options: GameOptions::default(),
+            layout: Vec::new(),
+            steps: Vec::new(),
+        };
+        for (n, line) in lines {
+            let unreadable = || {
+                // Translators: {line} is a line number in a replay file
+                gettext("Line {line} of the replay can't be read").replace("{line}", &n.to_string())
+            };
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            match words.as_slice() {
+                ["game", game_id] => replay.game_id = game_id.to_string(),
+                ["option", id, value] => replay
+                    .options
+                    .set(id, value.parse().map_err(|_| unreadable())?),
+                ["stack", name, cards @ ..] => {
+                    let cards = cards
+                        .iter()
+                        .map(|card| match card.strip_prefix(FACE_DOWN) {
+                            Some(card) => solver_card(card).map(|card| solver::card_flipped(&card)),
+                            None => solver_card(card),
+                        })
+                        .collect::<Option<Vec<u8>>>()
+                        .ok_or_else(unreadable)?;
+                    replay.layout.push((name.to_string(), cards));
+                }
+                ["step", time] => replay.steps.push(Transaction {
+                    time: Duration::from_millis(time.parse().map_err(|_| unreadable())?),
+                    ..Transaction::default()
+                }),
+                words => {
+                    let action = parse_action(words).ok_or_else(unreadable)?;
+                    replay
+                        .steps
+                        .last_mut()
+                        .ok_or_else(unreadable)?
+                        .actions
+                        .push(action);
+                }
+            }
+        }
+        if games::get_game(&replay.game_id).is_none() {
+            return Err(gettext(
+                "The replay is of a game this version of Solitaire doesn't have",
+            ));
+        }
+        replay.check()?;
+        Ok(replay)
+    }
+
+    // Makes the moves on the dealt cards, to be sure they can all be made
+    // before playing them on the board
+    fn check(&self) -> Result<(), String> {
+        let (stack_names, mut game_state) = self.solver_state();
+        for (n, step) in self.steps.iter().enumerate() {
+            for action in &step.actions {
+                perform(&stack_names, &mut game_state, action).map_err(|()| {
+                    // Translators: {step} is the number of a move in a replay
+                    gettext("Move {step} of the replay can't be made")
+                        .replace("{step}", &(n + 1).to_string())
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    // The stack names and solver state of the deal
+    fn solver_state(&self) -> (Vec<String>, Vec<Vec<u8>>) {
+        self.layout.iter().cloned().unzip()
+    }
+}
+
+impl std::fmt::Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{MAGIC} {VERSION}")?;
+        writeln!(f, "game {}", self.game_id)?;
+        for (id, value) in self.options.iter() {
+            writeln!(f, "option {id} {value}")?;
+        }
+        for (name, cards) in &self.layout {
+            let mut line = format!("stack {name}");
+            for card in cards {
+                line.push(' ');
+                if solver::is_flipped(card) {
+                    line.push(FACE_DOWN);
+                }
+                line.push_str(&solver::solver_card_to_name(*card));
+            }
+            writeln!(f, "{line}")?;
+        }
+        for step in &self.steps {
+            writeln!(f, "step {}", step.time.as_millis())?;
+            for action in &step.actions {
+                match action {
+                    Action::Move(move_) => {
+                        let mut line = format!(
+                            "move {} {} {}",
+                            move_.origin_stack, move_.card_name, move_.destination_stack
+                        );
+                        if move_.instruction == MoveInstruction::Flip {
+                            line.push_str(" flip");
+                        }
+                        writeln!(f, "{line}")?;
+                    }
+                    Action::Flip { stack, card_name } => writeln!(f, "flip {stack} {card_name}")?,
+                    Action::Unflip { stack, card_name } => {
+                        writeln!(f, "unflip {stack} {card_name}")?
+                    }
+                    Action::Score(points) => writeln!(f, "score {points}")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_action(words: &[&str]) -> Option<Action> {
+    let action = match words {
+        ["move", origin, card, destination, instruction @ ..] => {
+            let instruction = match instruction {
+                [] => MoveInstruction::None,
+                ["flip"] => MoveInstruction::Flip,
+                _ => return None,
+            };
+            solver_card(card)?;
+            Action::Move(runtime::create_move(origin, card, destination, instruction))
+        }
+        ["flip", stack, card] => {
+            solver_card(card)?;
+            Action::Flip {
+                stack: stack.to_string(),
+                card_name: card.to_string(),
+            }
+        }
+        ["unflip", stack, card] => {
+            solver_card(card)?;
+            Action::Unflip {
+                stack: stack.to_string(),
+                card_name: card.to_string(),
+            }
+        }
+        ["score", points] => Action::Score(points.parse().ok()?),
+        _ => return None,
+    };
+    Some(action)
+}
+
+// The face-up solver card with this name
+fn solver_card(name: &str) -> Option<u8> {
+    (0..=54).find(|card| solver::solver_card_to_name(*card).as_str() == name)
+}
+
+// Where the card is, as the index of its stack and its index in the stack
+fn find_card(
+    stack_names: &[String],
+    game_state: &[Vec<u8>],
+    stack: &str,
+    card_name: &str,
+) -> Result<(usize, usize), ()> {
+    let card = solver_card(card_name).ok_or(())?;
+    let stack = stack_names
+        .iter()
+        .position(|name| name == stack)
+        .ok_or(())?;
+    let index = game_state[stack]
+        .iter()
+        .position(|other| solver::solver_card_to_id(other) == card)
+        .ok_or(())?;
+    Ok((stack, index))
+}
+
+fn find_stack(stack_names: &[String], stack: &str) -> Result<usize, ()> {
+    stack_names.iter().position(|name| name == stack).ok_or(())
+}
+
+// Moves the card and the cards on top of it, like `runtime::perform_move_with_stacks`
+fn move_cards(
+    stack_names: &[String],
+    game_state: &mut [Vec<u8>],
+    move_: &Move,
+    origin: &str,
+    destination: &str,
+) -> Result<Vec<u8>, ()> {
+    let (origin, index) = find_card(stack_names, game_state, origin, &move_.card_name)?;
+    let destination = find_stack(stack_names, destination)?;
+    let mut moving = game_state[origin].split_off(index);
+    if move_.instruction == MoveInstruction::Flip {
+        moving.reverse();
+        moving.iter_mut().for_each(solver::flip);
+    }
+    game_state[destination].extend(&moving);
+    Ok(moving)
+}
+
+fn set_face_down(
+    stack_names: &[String],
+    game_state: &mut [Vec<u8>],
+    stack: &str,
+    card_name: &str,
+    face_down: bool,
+) -> Result<(), ()> {
+    let (stack, index) = find_card(stack_names, game_state, stack, card_name)?;
+    let card = &mut game_state[stack][index];
+    if solver::is_flipped(card) != face_down {
+        solver::flip(card);
+    }
+    Ok(())
+}
+
+// Makes a step's action on the solver state
+fn perform(stack_names: &[String], game_state: &mut [Vec<u8>], action: &Action) -> Result<(), ()> {
+    match action {
+        Action::Move(move_) => {
+            move_cards(
+                stack_names,
+                game_state,
+                move_,
+                &move_.origin_stack,
+                &move_.destination_stack,
+            )?;
+        }
+        Action::Flip { stack, card_name } => {
+            set_face_down(stack_names, game_state, stack, card_name, false)?
+        }
+        Action::Unflip { stack, card_name } => {
+            set_face_down(stack_names, game_state, stack, card_name, true)?
+        }
+        Action::Score(_) => {}
+    }
+    Ok(())
+}
+
+// Takes back a history action on the solver state, and returns it as it
+// was first made
+fn revert(
+    stack_names: &[String],
+    game_state: &mut [Vec<u8>],
+    action: &Action,
+) -> Result<Action, ()> {
+    match action {
+        Action::Move(move_) => {
+            let moved = move_cards(
+                stack_names,
+                game_state,
+                move_,
+                &move_.destination_stack,
+                &move_.origin_stack,
+            )?;
+            let mut made = move_.clone();
+            // Cards turned over on the way were reversed, so the move
+            // started at what is now the bottom card
+            if let Some(first) = moved.first() {
+                made.card_name = solver::solver_card_to_name(*first).to_string();
+            }
+            return Ok(Action::Move(made));
+        }
+        Action::Flip { stack, card_name } => {
+            set_face_down(stack_names, game_state, stack, card_name, true)?
+        }
+        Action::Unflip { stack, card_name } => {
+            set_face_down(stack_names, game_state, stack, card_name, false)?
+        }
+        Action::Score(_) => {}
+    }
+    Ok(action.clone())
+}
+
+/// Saves the game played so far as a replay file
+pub fn export(window: &SolitaireWindow) {
+    let Some(replay) = Replay::record(&window.get_gameboard()) else {
+        window.show_toast(&gettext("No game to export"));
+        return;
+    };
+    let dialog = gtk::FileDialog::builder()
+        .title(gettext("Export Replay"))
+        .initial_name(format!("{}.replay", replay.game_id))
+        .build();
+    glib::spawn_future_local(glib::clone!(
+        #[weak]
+        window,
+        async move {
+            let Ok(file) = dialog.save_future(Some(&window)).await else {
+                return;
+            };
+            let result = file
+                .replace_contents_future(
+                    replay.to_string(),
+                    None,
+                    false,
+                    gio::FileCreateFlags::REPLACE_DESTINATION,
+                )
+                .await;
+            match result {
+                Ok(_) => window.show_toast(&gettext("Replay exported")),
+                Err((_, error)) => {
+                    glib::g_warning!("solitaire", "Failed to save replay: {error}");
+                    window.show_toast(&gettext("Couldn't save the replay"));
+                }
+            }
+        }
+    ));
+}
+
+/// Asks for a replay file and plays it back
+pub fn open(window: &SolitaireWindow) {
+    let dialog = gtk::FileDialog::builder()
+        .title(gettext("Open Replay"))
+        .build();
+    glib::spawn_future_local(glib::clone!(
+        #[weak]
+        window,
+        async move {
+            if let Ok(file) = dialog.open_future(Some(&window)).await {
+                open_file(&window, file);
+            }
+        }
+    ));
+}
+
+/// Plays back the replay in the file
+pub fn open_file(window: &SolitaireWindow, file: gio::File) {
+    glib::spawn_future_local(glib::clone!(
+        #[weak]
+        window,
+        async move {
+            let replay = match file.load_contents_future().await {
+                Ok((contents, _)) => match std::str::from_utf8(&contents) {
+                    Ok(text) => Replay::parse(text),
+                    Err(_) => Err(gettext("The file is not a replay")),
+                },
+                Err(error) => {
+                    glib::g_warning!("solitaire", "Failed to read replay: {error}");
+                    Err(gettext("Couldn't read the replay"))
+                }
+            };
+            match replay {
+                Ok(replay) => window.leave_game(move |window| {
+                    window.show_chooser();
+                    play(window, &replay);
+                }),
+                Err(message) => window.show_toast(&message),
+            }
+        }
+    ));
+}
+
+// Deals the replay's cards on the board and starts playing it
+fn play(window: &SolitaireWindow, replay: &Replay) {
+    let Some(game) = games::get_game(&replay.game_id) else {
+        return;
+    };
+    let game_board = window.get_gameboard();
+    window.show_replay(game);
+    games::load_game_with_options(game, &replay.options, glib::random_int(), &game_board);
+    if !runtime::set_layout(&game_board, &replay.layout) {
+        window.leave_game(SolitaireWindow::show_chooser);
+        window.show_toast(&gettext("The replay doesn't fit the game"));
+        return;
+    }
+    window.set_hint_drop_enabled(false);
+    runtime::update_redo_actions(window);
+    player::open_replay(&game_board, replay.steps.clone());
+}
+
+/// Writes a replay out and reads it back, to check nothing is lost on the way
+pub fn test_round_trip() {
+    let layout = vec![
+        (
+            "tableau_0".to_string(),
+            vec![
+                solver::card_name_to_solver("club_2", true),
+                solver::card_name_to_solver("heart_king", false),
+            ],
+        ),
+        (
+            "tableau_1".to_string(),
+            vec![solver::card_name_to_solver("spade_queen", false)],
+        ),
+        ("foundation_0".to_string(), Vec::new()),
+    ];
+    let mut options = GameOptions::default();
+    options.set("example-rule", true);
+    let steps = vec![Transaction {
+        actions: vec![
+            Action::Move(runtime::create_move(
+                "tableau_1",
+                "spade_queen",
+                "tableau_0",
+                MoveInstruction::None,
+            )),
+            Action::Score(5),
+        ],
+        position: Vec::new(),
+        time: Duration::from_millis(1520),
+    }];
+    let replay = Replay {
+        game_id: games::GAMES[0].id.to_string(),
+        options,
+        layout,
+        steps,
+    };
+
+    let text = replay.to_string();
+    assert_eq!(
+        Replay::parse(&text).as_ref(),
+        Ok(&replay),
+        "replay round trip mismatch for\n{text}"
+    );
+
+    // Recording a replay takes the moves back; they must come out as made
+    let (stack_names, mut game_state) = replay.solver_state();
+    let flip_move = Action::Move(runtime::create_move(
+        "tableau_0",
+        "club_2",
+        "foundation_0",
+        MoveInstruction::Flip,
+    ));
+    perform(&stack_names, &mut game_state, &flip_move).unwrap();
+    let Action::Move(mut undo_ready) = flip_move.clone() else {
+        unreachable!();
+    };
+    // Like `runtime::perform_move_with_stacks`, history names the top card
+    undo_ready.card_name = "heart_king".to_string();
+    let made = revert(&stack_names, &mut game_state, &Action::Move(undo_ready)).unwrap();
+    assert_eq!(made, flip_move, "replay revert mismatch");
+    assert_eq!(
+        game_state,
+        replay.solver_state().1,
+        "replay revert didn't restore the layout"
+    );
+}
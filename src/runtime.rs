@@ -18,9 +18,22 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use crate::{card::Card, card_stack::CardStack, games};
-use gtk::prelude::{ActionMapExt, Cast, IsA, ListModelExt, WidgetExt};
-use gtk::{gio, glib, DragSource, GestureClick};
+use crate::{
+    automation, card::Card, card_stack::CardStack, clicks, game_board::GameBoard, games, keyboard,
+    renderer,
+};
+use gettextrs::gettext;
+use gtk::prelude::{
+    AccessibleExt, ActionMapExt, Cast, CastNone, IsA, ListModelExt, SettingsExt, TextureExt,
+    WidgetExt,
+};
+use gtk::{gdk, gio, glib, DragSource, GestureClick};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Move {
@@ -28,7 +41,6 @@ pub struct Move {
     pub card_name: String,
     pub destination_stack: String,
     pub instruction: MoveInstruction,
-    pub flip_index: Option<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -48,7 +60,6 @@ pub fn create_move(
         card_name: card_name.to_string(),
         destination_stack: destination_stack.to_string(),
         instruction,
-        flip_index: None,
     }
 }
 
@@ -63,28 +74,149 @@ pub fn move_from_strings(
         card_name,
         destination_stack,
         instruction,
-        flip_index: None,
     }
 }
 
-use std::cell::{Cell, RefCell};
-use std::time::Duration;
-
-thread_local! {
-    static STACK_NAMES: RefCell<Vec<String>> = RefCell::new(Vec::new());
-    static STACKS: RefCell<Vec<CardStack>> = RefCell::new(Vec::new());
-    static SOLUTION_MOVES: RefCell<Vec<Move>> = RefCell::new(Vec::new());
-    static HISTORY: RefCell<Vec<Move>> = RefCell::new(Vec::new());
-    static UNDO_HISTORY: RefCell<Vec<Move>> = RefCell::new(Vec::new());
-    static N_DEALS: Cell<u8> = Cell::new(0);
-    static CARDS: RefCell<Vec<Card>> = RefCell::new(Vec::new());
-    static IS_WON_FN: RefCell<Option<Box<dyn FnMut(&mut games::solver::State) -> bool>>> = RefCell::new(None);
+/// One atomic change to the board, the building block of a history entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Cards were moved from one stack to another
+    Move(Move),
+    /// A card was turned face up
+    Flip { stack: String, card_name: String },
+    /// A card was turned face down
+    Unflip { stack: String, card_name: String },
+    /// The score went up or down by this many points
+    Score(i32),
+}
+
+/// Everything that happened in response to one input. Undo and redo treat
+/// a transaction as a single step.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transaction {
+    pub actions: Vec<Action>,
+    /// The solver state the game was left in
+    pub position: Vec<Vec<u8>>,
+    /// How long after the deal the transaction was made
+    pub time: Duration,
+}
+
+impl Transaction {
+    /// The moves of this step, without the flips and score changes they caused
+    pub fn moves(&self) -> impl Iterator<Item = &Move> {
+        self.actions.iter().filter_map(|action| match action {
+            Action::Move(move_) => Some(move_),
+            _ => None,
+        })
+    }
+}
+
+const AUTOPLAY_DELAY: Duration = Duration::from_millis(150);
+const RERENDER_DELAY: Duration = Duration::from_millis(250);
+// Card widths are rounded up to a multiple of this, so small changes in size reuse textures
+const CARD_WIDTH_STEP: i32 = 32;
+const CACHED_CARD_WIDTHS: usize = 3;
+
+/// How long to wait between the moves of a sequence, so that each card lands
+/// before the next one takes off
+pub fn step_delay(game_board: &GameBoard, min_delay: Duration) -> Duration {
+    Duration::from_millis(game_board.animation_duration().into()).max(min_delay)
+}
+
+pub type WonFn = Box<dyn FnMut(&mut games::solver::State) -> bool + Send>;
+
+/// The state of the game played on one board. Every window owns a board,
+/// so games in different windows never share history or solver threads.
+pub struct Session {
+    pub game: RefCell<Option<Box<dyn games::Game>>>,
+    /// The id of the game being played and the rules it was dealt with
+    pub game_id: Cell<&'static str>,
+    pub options: RefCell<games::GameOptions>,
+    /// The seed the cards were dealt with
+    pub seed: Cell<u32>,
+    solution_moves: RefCell<Vec<Move>>,
+    // The hints for the current position and which one is shown next
+    hints: RefCell<Vec<Move>>,
+    hint_index: Cell<usize>,
+    // The solver state the game was dealt in
+    start_position: RefCell<Vec<Vec<u8>>>,
+    started: Cell<Instant>,
+    history: RefCell<Vec<Transaction>>,
+    undo_history: RefCell<Vec<Transaction>>,
+    // Every line of play, the branches of the undo tree from the deal to where
+    // they end. The slot of the line being played is empty, as that one is
+    // kept in history and undo_history.
+    lines: RefCell<Vec<Option<Vec<Transaction>>>>,
+    current_line: Cell<usize>,
+    transaction: RefCell<Transaction>,
+    transaction_depth: Cell<u32>,
+    autoplaying: Cell<bool>,
+    score: Cell<i32>,
+    cards: RefCell<Vec<Card>>,
+    theme: RefCell<String>,
+    card_theme: RefCell<Option<Rc<renderer::CardTheme>>>,
+    // The width the cards are rendered at, and textures for recently used widths
+    card_width: Cell<i32>,
+    card_textures: RefCell<Vec<(i32, Rc<Vec<gdk::Texture>>)>>,
+    rerender_source: RefCell<Option<glib::SourceId>>,
+    is_won_fn: RefCell<Option<WonFn>>,
     // Re-solve multithreading
-    static LATEST_SOLVING: Cell<usize> = Cell::new(0);
-    static FIRST_UNSOLVABLE: Cell<usize> = Cell::new(usize::MAX);
-    static FIRST_UNSOLVABLE_HISTORY: RefCell<Vec<Move>> = RefCell::new(Vec::new());
-    static NOTIFY_UNSOLVABLE: Cell<bool> = Cell::new(true);
-    static SOLVER_THREADS: RefCell<Vec<std::thread::JoinHandle<()>>> = RefCell::new(Vec::new());
+    first_unsolvable: Cell<usize>,
+    first_unsolvable_history: RefCell<Vec<Move>>,
+    notify_unsolvable: Cell<bool>,
+    solver_threads: RefCell<Vec<std::thread::JoinHandle<()>>>,
+    should_stop: Arc<AtomicBool>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            game: RefCell::new(None),
+            game_id: Cell::new(""),
+            options: RefCell::new(games::GameOptions::default()),
+            seed: Cell::new(0),
+            solution_moves: RefCell::new(Vec::new()),
+            hints: RefCell::new(Vec::new()),
+            hint_index: Cell::new(0),
+            start_position: RefCell::new(Vec::new()),
+            started: Cell::new(Instant::now()),
+            history: RefCell::new(Vec::new()),
+            undo_history: RefCell::new(Vec::new()),
+            lines: RefCell::new(vec![None]),
+            current_line: Cell::new(0),
+            transaction: RefCell::new(Transaction::default()),
+            transaction_depth: Cell::new(0),
+            autoplaying: Cell::new(false),
+            score: Cell::new(0),
+            cards: RefCell::new(Vec::new()),
+            theme: RefCell::new(String::new()),
+            card_theme: RefCell::new(None),
+            card_width: Cell::new(0),
+            card_textures: RefCell::new(Vec::new()),
+            rerender_source: RefCell::new(None),
+            is_won_fn: RefCell::new(None),
+            first_unsolvable: Cell::new(usize::MAX),
+            first_unsolvable_history: RefCell::new(Vec::new()),
+            notify_unsolvable: Cell::new(true),
+            solver_threads: RefCell::new(Vec::new()),
+            should_stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl Session {
+    pub fn set_should_stop(&self, should_stop: bool) {
+        self.should_stop.store(should_stop, Ordering::SeqCst);
+    }
+
+    pub fn get_should_stop(&self) -> bool {
+        self.should_stop.load(Ordering::Relaxed)
+    }
+
+    /// The flag solver threads of this session poll to know when to give up
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.should_stop.clone()
+    }
 }
 
 pub fn remove_drag(widget: &impl IsA<gtk::Widget>) {
@@ -123,7 +255,8 @@ pub fn connect_double_click(card: &Card) {
 
     let card_clone = card.to_owned();
     click.connect_pressed(move |_click, n_press, _x, _y| {
-        if n_press == 2 {
+        // A smart move already moved the card on the first click
+        if n_press == 2 && !clicks::is_smart_move() {
             games::on_double_click(&card_clone);
         }
     });
@@ -140,9 +273,9 @@ pub fn remove_click(widget: &impl IsA<gtk::Widget>) {
     }
 }
 
-pub fn perform_move(move_: &mut Move) {
-    let origin_stack = get_stack(&move_.origin_stack).unwrap();
-    let destination_stack = get_stack(&move_.destination_stack).unwrap();
+pub fn perform_move(game_board: &GameBoard, move_: &mut Move) {
+    let origin_stack = get_stack(game_board, &move_.origin_stack).unwrap();
+    let destination_stack = get_stack(game_board, &move_.destination_stack).unwrap();
     perform_move_with_stacks(move_, &origin_stack, &destination_stack);
 }
 
@@ -151,12 +284,16 @@ pub fn perform_move_with_stacks(
     origin_stack: &CardStack,
     destination_stack: &CardStack,
 ) {
+    let game_board = origin_stack.game_board();
+    let origin_children = origin_stack.observe_children();
+    let split_index = crate::card_stack::child_index(&*move_.card_name, &origin_children).unwrap();
+    let moving_cards: Vec<Card> = (split_index..origin_children.n_items())
+        .filter_map(|i| origin_children.item(i).and_downcast::<Card>())
+        .collect();
+    let starts = game_board.card_bounds(&moving_cards);
     match move_.instruction {
         MoveInstruction::Flip => {
             //Fixme
-            let origin_children = origin_stack.observe_children();
-            let split_index =
-                crate::card_stack::child_index(&*move_.card_name, &origin_children).unwrap();
             move_.card_name = origin_stack.last_child().unwrap().widget_name().to_string();
             for _ in split_index..origin_children.n_items() {
                 let card = origin_stack
@@ -165,102 +302,447 @@ pub fn perform_move_with_stacks(
                     .downcast::<Card>()
                     .unwrap();
                 card.flip();
-                card.unparent();
+                origin_stack.remove_card(&card);
                 destination_stack.add_card(&card);
                 card.remove_css_class("highlight");
             }
-            return;
         }
-        MoveInstruction::None => {}
+        MoveInstruction::None => {
+            let transfer_stack = origin_stack.split_to_new_on(&*move_.card_name);
+            destination_stack.merge_stack(&transfer_stack);
+        }
     }
-    let transfer_stack = origin_stack.split_to_new_on(&*move_.card_name);
-    destination_stack.merge_stack(&transfer_stack);
+    game_board.animate_cards(starts);
 }
 
-pub fn add_to_history(move_: Move) {
-    // Remove invalidated undo entries
-    let window = crate::window::SolitaireWindow::get_window().unwrap();
-    UNDO_HISTORY.with(|undos| undos.borrow_mut().clear());
-    HISTORY.with(|h| h.borrow_mut().push(move_.clone()));
+/// Opens a history entry. Everything recorded until the matching
+/// `end_transaction` is undone and redone as one step. Transactions nest,
+/// only the outermost one becomes a history entry.
+pub fn start_transaction(game_board: &GameBoard) {
+    keyboard::clear_selection(game_board);
+    clear_hints(game_board);
+    let session = game_board.session();
+    session
+        .transaction_depth
+        .set(session.transaction_depth.get() + 1);
+}
+
+pub fn end_transaction(game_board: &GameBoard) {
+    let session = game_board.session();
+    let depth = session.transaction_depth.get();
+    debug_assert!(depth > 0, "end_transaction: no transaction is open");
+    session.transaction_depth.set(depth.saturating_sub(1));
+    if depth > 1 || session.transaction.borrow().actions.is_empty() {
+        return;
+    }
+    if !session.autoplaying.get() {
+        let settings = gio::Settings::new(crate::APP_ID);
+        if settings.boolean("auto-finish") && games::finishing_move(game_board).is_some() {
+            autoplay(game_board, games::finishing_move);
+            return;
+        }
+        if settings.boolean("autoplay") && games::autoplay_move(game_board).is_some() {
+            autoplay(game_board, games::autoplay_move);
+            return;
+        }
+    }
+    let mut transaction = session.transaction.take();
+    let (stack_names, game_state) = get_solver_state(game_board);
+    transaction.position = game_state.clone();
+    transaction.time = session.started.get().elapsed();
+
+    // Undone moves are kept as a line of their own
+    let window = game_board.window().unwrap();
+    branch_off(session);
+    session.history.borrow_mut().push(transaction.clone());
     update_redo_actions(&window);
-    let (stack_names, game_state) = get_solver_state();
     let mut ghost_solver_state = games::solver::new_ghost_state(game_state.to_owned());
-    if IS_WON_FN.with_borrow_mut(|f| f.as_mut().unwrap()(&mut ghost_solver_state)) {
+    if session
+        .is_won_fn
+        .borrow_mut()
+        .as_mut()
+        .is_some_and(|is_won| is_won(&mut ghost_solver_state))
+    {
         window.won_dialog();
+        notify_ready(game_board);
         return;
     }
-    if let Some(solution_move) = get_hint() {
-        if solution_move == move_ {
-            SOLUTION_MOVES.with(|s| s.borrow_mut().remove(0));
-            return;
-        }
+    update_no_moves(game_board);
+    if follow_solution(session, &transaction) {
+        notify_ready(game_board);
+        return;
     }
-    re_solve_threaded(&window, stack_names, game_state);
+    re_solve_threaded(game_board, stack_names, game_state);
 }
 
-pub fn undo_last_move() {
-    if HISTORY.with(|h| h.borrow().is_empty()) {
+/// Shows the banner telling that no card can be moved anymore, if that's the
+/// case and the setting for it is on
+pub fn update_no_moves(game_board: &GameBoard) {
+    let Some(window) = game_board.window() else {
         return;
+    };
+    let settings = gio::Settings::new(crate::APP_ID);
+    let no_moves = settings.boolean("no-moves-banner")
+        && game_board.session().game.borrow().is_some()
+        && !games::has_legal_move(game_board);
+    window.set_no_moves(no_moves);
+}
+
+/// Plays the moves `next_move` comes up with one at a time, as part of the
+/// transaction that is being ended. Input is blocked until there are none left.
+fn autoplay(game_board: &GameBoard, next_move: fn(&GameBoard) -> Option<Move>) {
+    let session = game_board.session();
+    session.autoplaying.set(true);
+    start_transaction(game_board);
+    game_board.block_input();
+    if let Some(window) = game_board.window() {
+        window.set_hint_drop_enabled(false);
+        window.update_timeline();
+        for action in ["undo", "redo"] {
+            window
+                .lookup_action(action)
+                .unwrap()
+                .downcast::<gio::SimpleAction>()
+                .unwrap()
+                .set_enabled(false);
+        }
     }
-    HISTORY.with(|history| {
-        let mut history = history.borrow_mut();
-        let mut last_entry = history.pop().unwrap();
-        let destination_stack = get_stack(&last_entry.origin_stack).unwrap();
-        let origin_stack = get_stack(&last_entry.destination_stack).unwrap();
-        games::pre_undo_drag(&destination_stack, &origin_stack, &mut last_entry);
-        perform_move_with_stacks(&mut last_entry, &origin_stack, &destination_stack);
-        if SOLUTION_MOVES.with(|s| !s.borrow().is_empty()) {
-            // Fixme: This will make won games be re-solved
-            SOLUTION_MOVES.with(|s| s.borrow_mut().insert(0, last_entry.clone()));
+    glib::spawn_future_local(glib::clone!(
+        #[weak]
+        game_board,
+        async move {
+            loop {
+                glib::timeout_future(step_delay(&game_board, AUTOPLAY_DELAY)).await;
+                if !game_board.session().autoplaying.get() {
+                    // The game was unloaded
+                    game_board.unblock_input();
+                    return;
+                }
+                let Some(mut move_) = next_move(&game_board) else {
+                    break;
+                };
+                let origin_stack = get_stack(&game_board, &move_.origin_stack).unwrap();
+                let destination_stack = get_stack(&game_board, &move_.destination_stack).unwrap();
+                perform_move_with_stacks(&mut move_, &origin_stack, &destination_stack);
+                add_to_history(&game_board, &move_);
+                games::drag_completed(&origin_stack, &destination_stack, &move_);
+            }
+            game_board.unblock_input();
+            end_transaction(&game_board);
+            game_board.session().autoplaying.set(false);
+            if let Some(window) = game_board.window() {
+                window.update_timeline();
+            }
         }
-        UNDO_HISTORY.with(|undos| undos.borrow_mut().push(last_entry));
-    });
+    ));
 }
 
-fn undo_many(last_index: usize) {
-    if HISTORY.with(|h| h.borrow().is_empty()) {
-        return;
+fn record(game_board: &GameBoard, action: Action) {
+    let session = game_board.session();
+    if session.transaction_depth.get() == 0 {
+        // Actions recorded outside of a transaction are steps of their own
+        start_transaction(game_board);
+        session.transaction.borrow_mut().actions.push(action);
+        end_transaction(game_board);
+    } else {
+        session.transaction.borrow_mut().actions.push(action);
+    }
+}
+
+/// Records a move that was already performed in the open transaction
+pub fn add_to_history(game_board: &GameBoard, move_: &Move) {
+    record(game_board, Action::Move(move_.clone()));
+    automation::emit_moved(move_);
+    if let Some(message) = describe_move(game_board, move_) {
+        game_board.announce(&message, gtk::AccessibleAnnouncementPriority::Medium);
     }
-    HISTORY.with(|history| {
-        let mut history = history.borrow_mut();
-        for _ in last_index..history.len() {
-            let mut last_entry = history.pop().unwrap();
-            let destination_stack = get_stack(&last_entry.origin_stack).unwrap();
-            let origin_stack = get_stack(&last_entry.destination_stack).unwrap();
-            games::pre_undo_drag(&destination_stack, &origin_stack, &mut last_entry);
-            perform_move_with_stacks(&mut last_entry, &origin_stack, &destination_stack);
-            UNDO_HISTORY.with(|undos| undos.borrow_mut().push(last_entry));
-        }
-    });
 }
 
-pub fn redo_first_move() {
-    if UNDO_HISTORY.with(|undos| undos.borrow().is_empty()) {
+/// A move as screen readers say it, like “Queen of Hearts to Tableau 3”
+pub fn describe_move(game_board: &GameBoard, move_: &Move) -> Option<String> {
+    let card = game_board
+        .session()
+        .cards
+        .borrow()
+        .iter()
+        .find(|card| card.widget_name() == move_.card_name)?
+        .clone();
+    let destination = get_stack(game_board, &move_.destination_stack)?;
+    Some(
+        // Translators: a move, like “Queen of Hearts to Tableau 3”
+        gettext("{card} to {stack}")
+            .replace("{card}", &card.spoken_name())
+            .replace("{stack}", &destination.spoken_name()),
+    )
+}
+
+/// Turns a card over and records it in the open transaction
+pub fn turn_card(game_board: &GameBoard, card: &Card, face_up: bool) {
+    if card.is_face_up() == face_up {
         return;
     }
-    UNDO_HISTORY.with(|undos| {
-        let mut first_entry = undos.borrow_mut().pop().unwrap();
-        let origin_stack = get_stack(&first_entry.origin_stack).unwrap();
-        let destination_stack = get_stack(&first_entry.destination_stack).unwrap();
-        perform_move(&mut first_entry);
-        games::drag_completed(&origin_stack, &destination_stack, &mut first_entry);
-        HISTORY.with(|history| history.borrow_mut().push(first_entry.clone()));
-        if let Some(solution_move) = get_hint() {
-            if solution_move == first_entry {
-                SOLUTION_MOVES.with(|s| s.borrow_mut().remove(0));
-                return;
+    let stack = card.stack().unwrap().widget_name().to_string();
+    let card_name = card.widget_name().to_string();
+    if face_up {
+        card.flip_to_face();
+        record(game_board, Action::Flip { stack, card_name });
+        // Translators: a card was turned face up, like “Turned over 7 of Clubs”
+        let message = gettext("Turned over {card}").replace("{card}", &card.spoken_name());
+        game_board.announce(&message, gtk::AccessibleAnnouncementPriority::Medium);
+    } else {
+        card.flip_to_back();
+        record(game_board, Action::Unflip { stack, card_name });
+    }
+}
+
+pub fn get_score(game_board: &GameBoard) -> i32 {
+    game_board.session().score.get()
+}
+
+fn set_score(game_board: &GameBoard, score: i32) {
+    game_board.session().score.set(score);
+    if let Some(window) = game_board.window() {
+        window.update_score();
+    }
+}
+
+/// Changes the score and records it in the open transaction
+pub fn add_score(game_board: &GameBoard, points: i32) {
+    set_score(game_board, get_score(game_board) + points);
+    record(game_board, Action::Score(points));
+}
+
+/// Removes the moves of `transaction` from the solution if they were the next ones in it
+fn follow_solution(session: &Session, transaction: &Transaction) -> bool {
+    let mut solution = session.solution_moves.borrow_mut();
+    let n_moves = transaction.moves().count();
+    if n_moves == 0
+        || solution.len() < n_moves
+        || !transaction.moves().eq(solution.iter().take(n_moves))
+    {
+        return false;
+    }
+    solution.drain(..n_moves);
+    true
+}
+
+fn get_card(game_board: &GameBoard, stack: &str, card_name: &str) -> Card {
+    let stack = get_stack(game_board, stack).unwrap();
+    get_child(&stack, card_name)
+        .expect("get_card: Couldn't find card")
+        .downcast::<Card>()
+        .unwrap()
+}
+
+/// Reverts the actions of a transaction, last one first
+fn revert(game_board: &GameBoard, transaction: &mut Transaction) {
+    for action in transaction.actions.iter_mut().rev() {
+        match action {
+            Action::Move(move_) => {
+                let destination_stack = get_stack(game_board, &move_.origin_stack).unwrap();
+                let origin_stack = get_stack(game_board, &move_.destination_stack).unwrap();
+                games::pre_undo_drag(&destination_stack, &origin_stack, move_);
+                perform_move_with_stacks(move_, &origin_stack, &destination_stack);
             }
+            Action::Flip { stack, card_name } => {
+                get_card(game_board, stack, card_name).flip_to_back();
+            }
+            Action::Unflip { stack, card_name } => {
+                get_card(game_board, stack, card_name).flip_to_face();
+            }
+            Action::Score(points) => set_score(game_board, get_score(game_board) - *points),
         }
-        let (stack_names, game_state) = get_solver_state();
-        re_solve_threaded(
-            &crate::window::SolitaireWindow::get_window().unwrap(),
-            stack_names,
-            game_state,
-        );
-    });
+    }
+}
+
+/// Performs the actions of a transaction again, in the order they first happened
+fn replay(game_board: &GameBoard, transaction: &mut Transaction) {
+    for action in transaction.actions.iter_mut() {
+        match action {
+            Action::Move(move_) => {
+                let origin_stack = get_stack(game_board, &move_.origin_stack).unwrap();
+                let destination_stack = get_stack(game_board, &move_.destination_stack).unwrap();
+                perform_move_with_stacks(move_, &origin_stack, &destination_stack);
+                games::post_redo_drag(&origin_stack, &destination_stack, move_);
+            }
+            Action::Flip { stack, card_name } => {
+                get_card(game_board, stack, card_name).flip_to_face();
+            }
+            Action::Unflip { stack, card_name } => {
+                get_card(game_board, stack, card_name).flip_to_back();
+            }
+            Action::Score(points) => set_score(game_board, get_score(game_board) + *points),
+        }
+    }
+}
+
+pub fn undo_last_move(game_board: &GameBoard) {
+    keyboard::clear_selection(game_board);
+    clear_hints(game_board);
+    let session = game_board.session();
+    let Some(mut last_entry) = session.history.borrow_mut().pop() else {
+        return;
+    };
+    revert(game_board, &mut last_entry);
+    update_no_moves(game_board);
+    if !session.solution_moves.borrow().is_empty() {
+        // Fixme: This will make won games be re-solved
+        session
+            .solution_moves
+            .borrow_mut()
+            .splice(0..0, last_entry.moves().cloned());
+    }
+    session.undo_history.borrow_mut().push(last_entry);
+    notify_ready(game_board);
+}
+
+fn undo_many(game_board: &GameBoard, last_index: usize) {
+    let session = game_board.session();
+    let history_len = session.history.borrow().len();
+    for _ in last_index..history_len {
+        let Some(mut last_entry) = session.history.borrow_mut().pop() else {
+            return;
+        };
+        revert(game_board, &mut last_entry);
+        session.undo_history.borrow_mut().push(last_entry);
+    }
+}
+
+// Keeps the moves that were undone as a line of play of their own, and starts
+// a new line for the move that replaces them
+fn branch_off(session: &Session) {
+    let undone = session.undo_history.take();
+    if undone.is_empty() {
+        return;
+    }
+    let mut line = session.history.borrow().clone();
+    line.extend(undone.into_iter().rev());
+    let mut lines = session.lines.borrow_mut();
+    lines[session.current_line.get()] = Some(line);
+    lines.push(None);
+    session.current_line.set(lines.len() - 1);
+}
+
+// How many moves two lines of play have in common
+fn shared_moves(a: &[Transaction], b: &[Transaction]) -> usize {
+    a.iter()
+        .zip(b)
+        .take_while(|(a, b)| a.position == b.position)
+        .count()
+}
+
+/// The line of play being played, from the deal to its last move
+fn current_line(session: &Session) -> Vec<Transaction> {
+    let mut line = session.history.borrow().clone();
+    line.extend(session.undo_history.borrow().iter().rev().cloned());
+    line
+}
+
+/// How many moves of the current line have been made, and how many it has
+pub fn get_timeline(game_board: &GameBoard) -> (usize, usize) {
+    let session = game_board.session();
+    let made = session.history.borrow().len();
+    (made, made + session.undo_history.borrow().len())
+}
+
+/// Every line of play as its number of moves and the move it leaves the
+/// current line after, with the index of the current line
+pub fn get_lines(game_board: &GameBoard) -> (Vec<(usize, usize)>, usize) {
+    let session = game_board.session();
+    let current = current_line(session);
+    let lines = session
+        .lines
+        .borrow()
+        .iter()
+        .map(|line| match line {
+            Some(line) => (line.len(), shared_moves(&current, line)),
+            None => (current.len(), current.len()),
+        })
+        .collect();
+    (lines, session.current_line.get())
+}
+
+/// Undoes or redoes moves of the current line until the first `n_moves` are made
+pub fn go_to_move(game_board: &GameBoard, n_moves: usize) {
+    let session = game_board.session();
+    // Autoplay is adding to a transaction that isn't in the history yet
+    if is_autoplaying(game_board) {
+        return;
+    }
+    keyboard::clear_selection(game_board);
+    clear_hints(game_board);
+    let made = session.history.borrow().len();
+    if n_moves < made {
+        undo_many(game_board, n_moves);
+    }
+    for _ in made..n_moves {
+        let Some(mut entry) = session.undo_history.borrow_mut().pop() else {
+            break;
+        };
+        replay(game_board, &mut entry);
+        session.history.borrow_mut().push(entry);
+    }
+
+    // The solution was for another position
+    session.solution_moves.borrow_mut().clear();
+    update_no_moves(game_board);
+    if let Some(window) = game_board.window() {
+        window.set_hint_drop_enabled(false);
+        update_redo_actions(&window);
+    }
+    let (stack_names, game_state) = get_solver_state(game_board);
+    re_solve_threaded(game_board, stack_names, game_state);
+}
+
+/// Goes back to where the line of play leaves the current one and plays it
+/// to its end
+pub fn switch_line(game_board: &GameBoard, line: usize) {
+    let session = game_board.session();
+    if line == session.current_line.get() || is_autoplaying(game_board) {
+        return;
+    }
+    let Some(target) = session
+        .lines
+        .borrow_mut()
+        .get_mut(line)
+        .and_then(Option::take)
+    else {
+        return;
+    };
+    let current = current_line(session);
+    let fork = shared_moves(&current, &target);
+    keyboard::clear_selection(game_board);
+    clear_hints(game_board);
+    undo_many(game_board, fork);
+    session.lines.borrow_mut()[session.current_line.get()] = Some(current);
+    session.current_line.set(line);
+    session
+        .undo_history
+        .replace(target[fork..].iter().rev().cloned().collect());
+    go_to_move(game_board, target.len());
+}
+
+pub fn redo_first_move(game_board: &GameBoard) {
+    keyboard::clear_selection(game_board);
+    clear_hints(game_board);
+    let session = game_board.session();
+    let Some(mut first_entry) = session.undo_history.borrow_mut().pop() else {
+        return;
+    };
+    replay(game_board, &mut first_entry);
+    update_no_moves(game_board);
+    session.history.borrow_mut().push(first_entry.clone());
+    if follow_solution(session, &first_entry) {
+        notify_ready(game_board);
+        return;
+    }
+    let (stack_names, game_state) = get_solver_state(game_board);
+    re_solve_threaded(game_board, stack_names, game_state);
 }
 
 pub fn update_redo_actions(window: &crate::window::SolitaireWindow) {
+    let game_board = window.get_gameboard();
+    let session = game_board.session();
     let undo_action = window
         .lookup_action("undo")
         .unwrap()
@@ -271,166 +753,520 @@ pub fn update_redo_actions(window: &crate::window::SolitaireWindow) {
         .unwrap()
         .downcast::<gio::SimpleAction>()
         .unwrap();
-    HISTORY.with(|h| undo_action.set_enabled(!h.borrow().is_empty()));
-    UNDO_HISTORY.with(|undos| redo_action.set_enabled(!undos.borrow().is_empty()));
+    undo_action.set_enabled(!session.history.borrow().is_empty());
+    redo_action.set_enabled(!session.undo_history.borrow().is_empty());
+    window.update_timeline();
+}
+
+pub fn clear_history_and_moves(game_board: &GameBoard) {
+    let session = game_board.session();
+    session.history.borrow_mut().clear();
+    session.undo_history.borrow_mut().clear();
+    session.lines.replace(vec![None]);
+    session.current_line.set(0);
+    session.solution_moves.borrow_mut().clear();
+    session.transaction.replace(Transaction::default());
+    session.transaction_depth.set(0);
+    session.autoplaying.set(false);
+    set_score(game_board, 0);
+    session.first_unsolvable.set(usize::MAX);
+    session.first_unsolvable_history.replace(Vec::new());
+}
+
+pub fn get_stack(game_board: &GameBoard, name: &str) -> Option<CardStack> {
+    get_child(game_board, name)
+        .ok()?
+        .downcast::<CardStack>()
+        .ok()
+}
+
+pub fn get_solver_state(game_board: &GameBoard) -> (Vec<String>, Vec<Vec<u8>>) {
+    let mut names = Vec::new();
+    let mut solver_stacks = Vec::new();
+    let stacks = game_board.observe_children();
+    for i in 0..stacks.n_items() {
+        let stack = stacks.item(i).unwrap().downcast::<CardStack>().unwrap();
+        names.push(stack.widget_name().to_string());
+        solver_stacks.push(stack.get_solver_stack());
+    }
+    (names, solver_stacks)
 }
 
-pub fn clear_history_and_moves() {
-    HISTORY.with(|h| h.borrow_mut().clear());
-    UNDO_HISTORY.with(|undos| undos.borrow_mut().clear());
-    SOLUTION_MOVES.with(|s| s.borrow_mut().clear());
-    FIRST_UNSOLVABLE.set(usize::MAX);
-    FIRST_UNSOLVABLE_HISTORY.set(Vec::new());
+pub fn set_won_fn(game_board: &GameBoard, f: WonFn) {
+    game_board.session().is_won_fn.replace(Some(f));
 }
 
-pub fn get_stack(name: &str) -> Option<CardStack> {
-    let position = STACK_NAMES.with(|names| names.borrow().iter().position(|n| n == name))?;
-    STACKS.with(|stacks| stacks.borrow().get(position).cloned())
+pub fn set_cards(game_board: &GameBoard, cards: Vec<Card>) {
+    game_board.session().cards.replace(cards);
 }
 
-pub fn add_stack(name: &str, stack: &CardStack) {
-    STACK_NAMES.with(|names| names.borrow_mut().push(name.to_string()));
-    STACKS.with(|stacks| stacks.borrow_mut().push(stack.clone()));
+/// Remembers the current position as the one the game started in
+pub fn save_start_position(game_board: &GameBoard) {
+    let (_stack_names, game_state) = get_solver_state(game_board);
+    let session = game_board.session();
+    session.start_position.replace(game_state);
+    session.started.set(Instant::now());
 }
 
-pub fn clear_state() {
-    STACK_NAMES.with(|names| names.borrow_mut().clear());
-    STACKS.with(|stacks| stacks.borrow_mut().clear());
+/// Deals the cards again into the given stacks, as solver cards from the
+/// bottom of each stack up. Fails if the stacks aren't the ones of the game
+/// being played or the cards aren't all there.
+pub fn set_layout(game_board: &GameBoard, layout: &[(String, Vec<u8>)]) -> bool {
+    let (stack_names, game_state) = get_solver_state(game_board);
+    let mut wanted = layout
+        .iter()
+        .flat_map(|(_, stack)| stack.iter().map(games::solver::solver_card_to_id))
+        .collect::<Vec<u8>>();
+    let mut dealt = game_state
+        .iter()
+        .flatten()
+        .map(games::solver::solver_card_to_id)
+        .collect::<Vec<u8>>();
+    wanted.sort_unstable();
+    dealt.sort_unstable();
+    if wanted != dealt
+        || layout.len() != stack_names.len()
+        || layout.iter().any(|(name, _)| !stack_names.contains(name))
+    {
+        return false;
+    }
+
+    let mut cards = HashMap::new();
+    for stack in game_board.stacks() {
+        while let Some(card) = stack.last_card() {
+            stack.remove_card(&card);
+            cards.insert(card.widget_name().to_string(), card);
+        }
+    }
+    for (name, solver_stack) in layout {
+        let stack = get_stack(game_board, name).unwrap();
+        for solver_card in solver_stack {
+            let Some(card) = cards.remove(&*games::solver::solver_card_to_name(*solver_card))
+            else {
+                continue;
+            };
+            stack.add_card(&card);
+            if games::solver::is_flipped(solver_card) {
+                card.flip_to_back();
+            } else {
+                card.flip_to_face();
+            }
+        }
+    }
+    save_start_position(game_board);
+    update_no_moves(game_board);
+    true
+}
+
+/// Sets up the board in the given position as if it had been dealt that
+/// way, and looks for a solution from there
+pub fn set_position(game_board: &GameBoard, layout: &[(String, Vec<u8>)]) -> bool {
+    keyboard::clear_selection(game_board);
+    clear_hints(game_board);
+    if !set_layout(game_board, layout) {
+        return false;
+    }
+    clear_history_and_moves(game_board);
+    if let Some(window) = game_board.window() {
+        update_redo_actions(&window);
+    }
+    let (stack_names, game_state) = get_solver_state(game_board);
+    re_solve_threaded(game_board, stack_names, game_state);
+    true
 }
 
-pub fn get_solver_state() -> (Vec<String>, Vec<Vec<u8>>) {
-    let names = STACK_NAMES.with(|names| names.borrow().to_owned());
-    let stacks = STACKS.with(|stacks| {
-        let mut solver_stacks = Vec::new();
-        for stack in stacks.borrow().iter() {
-            solver_stacks.push(stack.get_solver_stack())
+/// Makes a recorded transaction again, or the one that was last stepped back over
+pub fn replay_transaction(game_board: &GameBoard, mut transaction: Transaction) {
+    keyboard::clear_selection(game_board);
+    clear_hints(game_board);
+    let session = game_board.session();
+    // Stepping back left it to be redone
+    session.undo_history.borrow_mut().pop();
+    replay(game_board, &mut transaction);
+    for move_ in transaction.moves() {
+        if let Some(message) = describe_move(game_board, move_) {
+            game_board.announce(&message, gtk::AccessibleAnnouncementPriority::Medium);
         }
-        solver_stacks
-    });
-    (names, stacks)
+    }
+    let (_stack_names, game_state) = get_solver_state(game_board);
+    transaction.position = game_state;
+    session.history.borrow_mut().push(transaction);
+    update_no_moves(game_board);
+    if let Some(window) = game_board.window() {
+        update_redo_actions(&window);
+    }
 }
 
-pub fn set_won_fn<F: FnMut(&mut games::solver::State) -> bool + 'static>(f: F) {
-    IS_WON_FN.set(Some(Box::new(f)));
+/// The positions the game has been in, from the deal to the current one,
+/// with the history entries that led from each to the next
+pub fn get_positions(game_board: &GameBoard) -> (Vec<Vec<Vec<u8>>>, Vec<Transaction>) {
+    let session = game_board.session();
+    let history = session.history.borrow().clone();
+    let mut positions = vec![session.start_position.borrow().clone()];
+    positions.extend(
+        history
+            .iter()
+            .map(|transaction| transaction.position.clone()),
+    );
+    (positions, history)
 }
 
-pub fn set_cards(cards: Vec<Card>) {
-    CARDS.set(cards);
+/// Undoes moves until only the first `n_entries` history entries are left
+/// and plays on from there with the given solution
+pub fn rewind_to(game_board: &GameBoard, n_entries: usize, solution: Vec<Move>) {
+    keyboard::clear_selection(game_board);
+    clear_hints(game_board);
+    undo_many(game_board, n_entries);
+    let has_solution = !solution.is_empty();
+    game_board.session().solution_moves.replace(solution);
+    update_no_moves(game_board);
+    if let Some(window) = game_board.window() {
+        update_redo_actions(&window);
+        window.set_hint_drop_enabled(has_solution);
+    }
+}
+
+pub fn get_cards(game_board: &GameBoard) -> Vec<Card> {
+    game_board.session().cards.borrow().to_owned()
+}
+
+/// The name of the theme the cards of this session were rendered with
+pub fn get_theme(game_board: &GameBoard) -> String {
+    game_board.session().theme.borrow().clone()
+}
+
+/// Loads a card theme off the main thread, then gives every card its new
+/// images at once
+pub fn load_theme(game_board: &GameBoard, theme_name: String) {
+    let session = game_board.session();
+    session.theme.replace(theme_name.clone());
+    let receiver = renderer::load_theme(
+        theme_name.clone(),
+        session.card_width.get(),
+        renderer::RenderOptions::from_settings(),
+    );
+    glib::spawn_future_local(glib::clone!(
+        #[weak]
+        game_board,
+        async move {
+            let window = game_board.window();
+            while let Ok(progress) = receiver.recv().await {
+                match progress {
+                    renderer::ThemeProgress::Rendering(fraction) => {
+                        if let Some(window) = &window {
+                            window.set_theme_progress(Some(fraction));
+                        }
+                    }
+                    renderer::ThemeProgress::Done(card_theme, textures) => {
+                        // Another theme was asked for in the meantime
+                        if *game_board.session().theme.borrow() == theme_name {
+                            set_theme(&game_board, card_theme, textures);
+                        }
+                    }
+                }
+            }
+            if let Some(window) = &window {
+                window.set_theme_progress(None);
+            }
+        }
+    ));
 }
 
-pub fn get_cards() -> Vec<Card> {
-    CARDS.with(|cards| cards.borrow().to_owned())
+fn set_theme(game_board: &GameBoard, card_theme: renderer::CardTheme, textures: Vec<gdk::Texture>) {
+    if textures.len() != 53 {
+        glib::g_warning!(
+            "solitaire",
+            "Couldn't render card theme {}",
+            card_theme.name
+        );
+        return;
+    }
+    let session = game_board.session();
+    let width = textures[0].width();
+    renderer::ASPECT.set(card_theme.aspect());
+    session.card_theme.replace(Some(Rc::new(card_theme)));
+    let textures = Rc::new(textures);
+    session
+        .card_textures
+        .replace(vec![(width, textures.clone())]);
+    session.card_width.set(width);
+    let back = textures.last().unwrap();
+    for card in session.cards.borrow().iter() {
+        card.set_textures(&textures[card.id()], back);
+    }
+    game_board.queue_resize();
+    resize_cards(game_board);
 }
 
-pub fn get_deals() -> u8 {
-    N_DEALS.get()
+/// Renders the cards again after the settings for how they look changed
+pub fn reload_cards(game_board: &GameBoard) {
+    let session = game_board.session();
+    session.card_textures.borrow_mut().clear();
+    session.card_width.set(0);
+    resize_cards(game_board);
 }
 
-pub fn update_deals(n: u8) {
-    N_DEALS.set(n);
+/// Renders the cards again at the size they are shown at, once the board has
+/// stopped changing size
+pub fn resize_cards(game_board: &GameBoard) {
+    let shown_width = game_board.card_width() * game_board.scale_factor();
+    if shown_width <= 0 {
+        return;
+    }
+    let width = (shown_width + CARD_WIDTH_STEP - 1) / CARD_WIDTH_STEP * CARD_WIDTH_STEP;
+    let session = game_board.session();
+    if width == session.card_width.get() {
+        return;
+    }
+    session.card_width.set(width);
+    if let Some(source) = session.rerender_source.take() {
+        source.remove();
+    }
+    let source = glib::timeout_add_local_once(
+        RERENDER_DELAY,
+        glib::clone!(
+            #[weak]
+            game_board,
+            move || {
+                game_board.session().rerender_source.take();
+                glib::spawn_future_local(async move {
+                    render_cards(&game_board, width).await;
+                });
+            }
+        ),
+    );
+    session.rerender_source.replace(Some(source));
 }
 
-pub fn get_hint() -> Option<Move> {
-    SOLUTION_MOVES.with(|moves| moves.borrow().first().cloned())
+async fn render_cards(game_board: &GameBoard, width: i32) {
+    let session = game_board.session();
+    let cached = session
+        .card_textures
+        .borrow()
+        .iter()
+        .find(|(cached_width, _)| *cached_width == width)
+        .map(|(_, textures)| textures.clone());
+    let textures = match cached {
+        Some(textures) => textures,
+        None => {
+            let Some(card_theme) = session.card_theme.borrow().clone() else {
+                return;
+            };
+            let options = renderer::RenderOptions::from_settings();
+            let Some(textures) = renderer::render_cards(&card_theme, width, options.clone()).await
+            else {
+                glib::g_warning!("solitaire", "Couldn't render the cards {width} pixels wide");
+                return;
+            };
+            // The theme or the settings may have changed while rendering
+            if !session
+                .card_theme
+                .borrow()
+                .as_ref()
+                .is_some_and(|current| Rc::ptr_eq(current, &card_theme))
+                || renderer::RenderOptions::from_settings() != options
+            {
+                return;
+            }
+            let textures = Rc::new(textures);
+            let mut cache = session.card_textures.borrow_mut();
+            cache.push((width, textures.clone()));
+            if cache.len() > CACHED_CARD_WIDTHS {
+                cache.remove(0);
+            }
+            textures
+        }
+    };
+    // Another size was asked for in the meantime
+    if session.card_width.get() != width {
+        return;
+    }
+    let back = textures.last().unwrap();
+    for card in session.cards.borrow().iter() {
+        card.set_textures(&textures[card.id()], back);
+    }
 }
-pub fn set_solution(moves: Vec<Move>) {
-    SOLUTION_MOVES.set(moves);
-    NOTIFY_UNSOLVABLE.set(true);
+
+pub fn get_hint(game_board: &GameBoard) -> Option<Move> {
+    game_board
+        .session()
+        .solution_moves
+        .borrow()
+        .first()
+        .cloned()
 }
 
-pub fn drop() {
-    let solution = SOLUTION_MOVES.with(|s| s.borrow().clone());
-    glib::spawn_future_local(async move {
-        for mut move_ in solution {
-            let origin_stack = get_stack(&move_.origin_stack).unwrap();
-            let destination_stack = get_stack(&move_.destination_stack).unwrap();
-            perform_move_with_stacks(&mut move_, &origin_stack, &destination_stack);
-            games::drag_completed(&origin_stack, &destination_stack, &mut move_);
-            add_to_history(move_);
-            glib::timeout_future(Duration::from_millis(300)).await;
+/// The hint to show next. The next move of the solution comes first, asking
+/// again goes through the other moves that can be made, the most promising first.
+pub fn next_hint(game_board: &GameBoard) -> Option<Move> {
+    let session = game_board.session();
+    if session.hints.borrow().is_empty() {
+        let mut hints: Vec<Move> = get_hint(game_board).into_iter().collect();
+        for move_ in games::candidate_moves(game_board) {
+            if !hints.contains(&move_) {
+                hints.push(move_);
+            }
         }
-    });
+        session.hints.replace(hints);
+        session.hint_index.set(0);
+    }
+    let hints = session.hints.borrow();
+    let index = session.hint_index.get() % hints.len().max(1);
+    session.hint_index.set(index + 1);
+    hints.get(index).cloned()
 }
 
-pub fn set_can_drop(can_drop: bool) {
-    crate::window::SolitaireWindow::get_window()
-        .unwrap()
-        .set_can_drop(can_drop);
+/// Forgets the hints for the current position and takes down the one shown
+pub fn clear_hints(game_board: &GameBoard) {
+    game_board.session().hints.borrow_mut().clear();
+    game_board.clear_hint();
 }
 
-fn re_solve_threaded(
-    window: &crate::window::SolitaireWindow,
-    stack_names: Vec<String>,
-    game_state: Vec<Vec<u8>>,
-) {
-    fn clear_and_abort_threads() {
-        games::solver::set_should_stop(true);
-        SOLVER_THREADS.with_borrow_mut(|t| t.clear());
+pub fn set_solution(game_board: &GameBoard, moves: Vec<Move>) {
+    let session = game_board.session();
+    // The solution's move goes first in the hints
+    session.hints.borrow_mut().clear();
+    session.solution_moves.replace(moves);
+    session.notify_unsolvable.set(true);
+}
+
+/// Makes a move on the player's behalf as a history entry of its own
+pub fn play_move(game_board: &GameBoard, move_: &mut Move) {
+    let origin_stack = get_stack(game_board, &move_.origin_stack).unwrap();
+    let destination_stack = get_stack(game_board, &move_.destination_stack).unwrap();
+    start_transaction(game_board);
+    perform_move_with_stacks(move_, &origin_stack, &destination_stack);
+    add_to_history(game_board, move_);
+    games::drag_completed(&origin_stack, &destination_stack, move_);
+    end_transaction(game_board);
+}
+
+/// Whether autoplay or auto-finish is making moves, with input blocked
+pub fn is_autoplaying(game_board: &GameBoard) -> bool {
+    game_board.session().autoplaying.get()
+}
+
+/// Whether moves are still being made or solved for after the last one
+pub fn is_busy(game_board: &GameBoard) -> bool {
+    let session = game_board.session();
+    session.autoplaying.get() || !session.solver_threads.borrow().is_empty()
+}
+
+/// Tells automation clients the board takes input again, once nothing is
+/// being played or solved for anymore
+pub fn notify_ready(game_board: &GameBoard) {
+    if !is_busy(game_board) {
+        automation::emit_ready();
     }
+}
 
+pub fn set_can_drop(game_board: &GameBoard, can_drop: bool) {
+    if let Some(window) = game_board.window() {
+        window.set_can_drop(can_drop);
+    }
+}
+
+fn re_solve_threaded(game_board: &GameBoard, stack_names: Vec<String>, game_state: Vec<Vec<u8>>) {
+    fn clear_and_abort_threads(session: &Session) {
+        session.set_should_stop(true);
+        session.solver_threads.borrow_mut().clear();
+    }
+
+    let Some(window) = game_board.window() else {
+        return;
+    };
     window.set_hint_drop_enabled(false);
     glib::spawn_future_local(glib::clone!(
         #[weak]
         window,
+        #[weak]
+        game_board,
         async move {
-            let discarded_solver_history = SOLUTION_MOVES.with(|s| s.borrow().clone());
-            let move_index = HISTORY.with(|h| h.borrow().len());
+            let session = game_board.session();
+            let discarded_solver_history = session.solution_moves.borrow().clone();
+            let move_index = session.history.borrow().len();
+            let Some((move_generator, is_won_fn)) = games::solver_fns(&game_board) else {
+                return;
+            };
+            let should_stop = session.stop_flag();
             let (sender, receiver) = async_channel::bounded(1);
             let t = std::thread::spawn(move || {
-                let result = games::re_solve(stack_names, game_state);
+                let result = games::re_solve(
+                    stack_names,
+                    game_state,
+                    move_generator,
+                    is_won_fn,
+                    &should_stop,
+                );
                 sender.send_blocking(result).unwrap();
             });
-            SOLVER_THREADS.with_borrow_mut(|s| s.push(t));
+            let thread_id = t.thread().id();
+            session.solver_threads.borrow_mut().push(t);
             while let Ok(result) = receiver.recv().await {
+                // The thread is done, whatever becomes of its result
+                session
+                    .solver_threads
+                    .borrow_mut()
+                    .retain(|t| t.thread().id() != thread_id);
                 if let Some(history) = result {
-                    if move_index < HISTORY.with_borrow(|h| h.len()) {
+                    if move_index < session.history.borrow().len() {
                         continue;
                     }
-                    clear_and_abort_threads();
-                    set_solution(history);
-                    if SOLUTION_MOVES.with(|s| s.borrow().is_empty()) {
-                        if move_index < FIRST_UNSOLVABLE.get() {
-                            FIRST_UNSOLVABLE.set(move_index);
-                            FIRST_UNSOLVABLE_HISTORY.set(discarded_solver_history.clone());
+                    clear_and_abort_threads(session);
+                    set_solution(&game_board, history);
+                    if session.solution_moves.borrow().is_empty() {
+                        if move_index < session.first_unsolvable.get() {
+                            session.first_unsolvable.set(move_index);
+                            session
+                                .first_unsolvable_history
+                                .replace(discarded_solver_history.clone());
                         }
                         continue;
                     }
-                    FIRST_UNSOLVABLE.set(usize::MAX);
+                    session.first_unsolvable.set(usize::MAX);
                     window.set_hint_drop_enabled(true);
                 } else {
-                    if move_index < FIRST_UNSOLVABLE.get() && !games::solver::get_should_stop() {
-                        FIRST_UNSOLVABLE.set(move_index);
-                        FIRST_UNSOLVABLE_HISTORY.set(discarded_solver_history.clone());
+                    if move_index < session.first_unsolvable.get() && !session.get_should_stop() {
+                        session.first_unsolvable.set(move_index);
+                        session
+                            .first_unsolvable_history
+                            .replace(discarded_solver_history.clone());
                     }
-                    let first_unsolvable = FIRST_UNSOLVABLE.get();
-                    if move_index == HISTORY.with_borrow(|h| h.len()) {
-                        if NOTIFY_UNSOLVABLE.get() {
-                            let window = window.clone();
-                            crate::window::SolitaireWindow::incompatible_move_dialog(
+                    let first_unsolvable = session.first_unsolvable.get();
+                    if move_index == session.history.borrow().len() {
+                        if session.notify_unsolvable.get() {
+                            let undo_board = game_board.clone();
+                            let keep_board = game_board.clone();
+                            let undo_window = window.clone();
+                            window.incompatible_move_dialog(
                                 move |_dialog, _response| {
                                     // Undo Button
-                                    undo_many(first_unsolvable - 1);
-                                    update_redo_actions(&window);
-                                    clear_and_abort_threads();
-                                    let first_unsolvable_h = FIRST_UNSOLVABLE_HISTORY.take();
+                                    let session = undo_board.session();
+                                    undo_many(&undo_board, first_unsolvable.saturating_sub(1));
+                                    update_redo_actions(&undo_window);
+                                    clear_and_abort_threads(session);
+                                    let first_unsolvable_h =
+                                        session.first_unsolvable_history.take();
                                     if !first_unsolvable_h.is_empty() {
-                                        window.set_hint_drop_enabled(true);
+                                        undo_window.set_hint_drop_enabled(true);
                                     }
-                                    SOLUTION_MOVES.set(first_unsolvable_h);
+                                    session.solution_moves.replace(first_unsolvable_h);
                                 },
                                 move |_dialog, _response| {
                                     // Keep Playing button
-                                    NOTIFY_UNSOLVABLE.set(false);
-                                    clear_and_abort_threads();
+                                    let session = keep_board.session();
+                                    session.notify_unsolvable.set(false);
+                                    clear_and_abort_threads(session);
                                 },
                             );
                         } else {
-                            clear_and_abort_threads();
+                            clear_and_abort_threads(session);
                         }
-                        FIRST_UNSOLVABLE.set(usize::MAX);
+                        session.first_unsolvable.set(usize::MAX);
                     }
                 }
             }
+            notify_ready(&game_board);
         }
     ));
 }
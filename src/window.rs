@@ -18,17 +18,15 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use crate::{card_stack::CardStack, game_board::GameBoard, games, runtime};
+use crate::{
+    analysis, automation, celebration, game_board::GameBoard, games, player, replay, runtime,
+};
 use adw::{prelude::*, subclass::prelude::*};
-use gettextrs::gettext;
+use gettextrs::{gettext, ngettext};
 use gtk::prelude::*;
 use gtk::{gio, glib};
 use lggs::prelude::*;
 
-thread_local! {
-    static SELF: std::cell::RefCell<Option<SolitaireWindow>> = std::cell::RefCell::new(None);
-}
-
 mod imp {
     use super::*;
 
@@ -49,6 +47,26 @@ mod imp {
         #[template_child]
         pub game_bin: TemplateChild<adw::Bin>,
         #[template_child]
+        pub theme_progress: TemplateChild<gtk::ProgressBar>,
+        #[template_child]
+        pub no_moves_banner: TemplateChild<adw::Banner>,
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub solution_bar: TemplateChild<gtk::ActionBar>,
+        #[template_child]
+        pub solution_take_over: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub solution_play: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub solution_speed: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        pub timeline: TemplateChild<gtk::Scale>,
+        #[template_child]
+        pub timeline_lines: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        pub score: TemplateChild<gtk::Label>,
+        #[template_child]
         pub search_bar: TemplateChild<gtk::SearchBar>,
         #[template_child]
         pub search_entry: TemplateChild<gtk::SearchEntry>,
@@ -72,6 +90,8 @@ mod imp {
         pub can_drop: std::cell::Cell<bool>,
         pub new_game_is_safe: std::cell::Cell<bool>,
         pub good_search: std::cell::Cell<bool>,
+        // Set while the timeline follows the game, so it doesn't move the game back
+        pub updating_timeline: std::cell::Cell<bool>,
     }
 
     #[glib::object_subclass]
@@ -105,12 +125,28 @@ mod imp {
             self.welcome
                 .get()
                 .set_icon_name(Some(crate::config::APP_ID));
-            SELF.set(Some(obj.clone()));
             self.game_bin.get().set_child(Some(&GameBoard::new()));
+            self.solution_speed
+                .get()
+                .connect_selected_notify(glib::clone!(
+                    #[weak]
+                    obj,
+                    move |speed| {
+                        if let Some(speed) = player::SPEEDS.get(speed.selected() as usize) {
+                            player::set_speed(&obj.get_gameboard(), *speed);
+                        }
+                    }
+                ));
         }
     }
     impl WidgetImpl for SolitaireWindow {}
-    impl WindowImpl for SolitaireWindow {}
+    impl WindowImpl for SolitaireWindow {
+        fn close_request(&self) -> glib::Propagation {
+            // Don't leave solver threads of this window running
+            self.obj().get_gameboard().session().set_should_stop(true);
+            self.parent_close_request()
+        }
+    }
     impl ApplicationWindowImpl for SolitaireWindow {}
     impl AdwApplicationWindowImpl for SolitaireWindow {}
 }
@@ -149,8 +185,13 @@ impl SolitaireWindow {
         let change_theme = move |theme_name: &str, widget: &gtk::Widget| {
             let picture = widget.to_owned().downcast::<gtk::Picture>().unwrap();
             let card_theme = renderer::get_card_theme(theme_name);
-            let renderer = rsvg::CairoRenderer::new(&card_theme.handle);
-            renderer::draw_theme_preview(theme_name, &card_theme, &renderer, &picture);
+            match card_theme.handle() {
+                Ok(handle) => {
+                    let renderer = rsvg::CairoRenderer::new(&handle);
+                    renderer::draw_theme_preview(theme_name, &card_theme, &renderer, &picture);
+                }
+                Err(error) => glib::g_warning!("solitaire", "Failed to load SVG: {error}"),
+            }
             settings.set("theme", theme_name.to_string());
             picture.upcast()
         };
@@ -159,8 +200,9 @@ impl SolitaireWindow {
         picture.set_margin_start(6);
         picture.set_margin_end(6);
 
-        let theme_dialog =
-            lggs::ThemeSelectorDialog::new(&renderer::THEME_NAMES, &theme_name, &picture);
+        let theme_names = renderer::theme_names();
+        let theme_names: Vec<&str> = theme_names.iter().map(String::as_str).collect();
+        let theme_dialog = lggs::ThemeSelectorDialog::new(&theme_names, &theme_name, &picture);
         change_theme(&theme_name, &picture.upcast::<gtk::Widget>());
 
         theme_dialog.set_content_height(350);
@@ -171,35 +213,87 @@ impl SolitaireWindow {
     }
 
     fn drop(&self) {
-        runtime::drop();
+        player::open(&self.get_gameboard());
     }
 
     fn hint(&self) {
-        if let Some(move_) = runtime::get_hint() {
+        let game_board = self.get_gameboard();
+        // Nothing to hint while cards are dealt, autoplayed or replayed
+        if game_board.session().game.borrow().is_none()
+            || runtime::is_autoplaying(&game_board)
+            || player::is_replay(&game_board)
+        {
+            return;
+        }
+        if let Some(move_) = runtime::next_hint(&game_board) {
             glib::g_message!("solitaire", "Hint: {:?}", move_);
-
-            let game_board = self.get_gameboard();
-
-            // Focus the source stack
-            if let Ok(source_stack) = runtime::get_child(&game_board, &*move_.origin_stack) {
-                let source_stack = source_stack.downcast::<CardStack>().unwrap();
-                source_stack.hint_card(move_.card_name);
+            if let Some(description) = runtime::describe_move(&game_board, &move_) {
+                // Translators: a suggested move, like “Hint: Queen of Hearts to Tableau 3”
+                let message = gettext("Hint: {move}").replace("{move}", &description);
+                game_board.announce(&message, gtk::AccessibleAnnouncementPriority::Medium);
             }
+            game_board.show_hint(&move_);
         } else {
-            println!("No hints available!");
+            self.show_toast(&gettext("No hints available"));
         }
     }
 
     fn undo(&self) {
-        runtime::undo_last_move();
+        player::pause(&self.get_gameboard());
+        runtime::undo_last_move(&self.get_gameboard());
         runtime::update_redo_actions(self);
     }
 
     fn redo(&self) {
-        runtime::redo_first_move();
+        player::pause(&self.get_gameboard());
+        runtime::redo_first_move(&self.get_gameboard());
         runtime::update_redo_actions(self);
     }
 
+    fn copy_position(&self) {
+        let game_board = self.get_gameboard();
+        if game_board.session().game.borrow().is_none() {
+            return;
+        }
+        let (stack_names, game_state) = runtime::get_solver_state(&game_board);
+        self.clipboard()
+            .set_text(&games::notation::format_position(&stack_names, &game_state));
+        self.show_toast(&gettext("Position copied"));
+    }
+
+    fn paste_position(&self) {
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to=window)]
+            self,
+            async move {
+                let game_board = window.get_gameboard();
+                let text = match window.clipboard().read_text_future().await {
+                    Ok(Some(text)) => text,
+                    _ => {
+                        window.show_toast(&gettext("No position to paste"));
+                        return;
+                    }
+                };
+                let layout = match games::notation::parse_position(&text) {
+                    Ok(layout) => layout,
+                    Err(message) => {
+                        window.show_toast(&message);
+                        return;
+                    }
+                };
+                if game_board.session().game.borrow().is_none() {
+                    return;
+                }
+                player::close(&game_board);
+                if runtime::set_position(&game_board, &layout) {
+                    window.show_toast(&gettext("Position pasted"));
+                } else {
+                    window.show_toast(&gettext("The position doesn't fit this game"));
+                }
+            }
+        ));
+    }
+
     fn setup_gactions(&self) {
         let appearance_action = gio::ActionEntry::builder("appearance")
             .activate(move |win: &Self, _, _| win.appearance())
@@ -210,6 +304,36 @@ impl SolitaireWindow {
         let hint_action = gio::ActionEntry::builder("hint")
             .activate(move |win: &Self, _, _| win.hint())
             .build();
+        let play_solution_action = gio::ActionEntry::builder("play-solution")
+            .activate(move |win: &Self, _, _| player::open(&win.get_gameboard()))
+            .build();
+        let solution_play_action = gio::ActionEntry::builder("solution-play")
+            .activate(move |win: &Self, _, _| player::toggle(&win.get_gameboard()))
+            .build();
+        let solution_forward_action = gio::ActionEntry::builder("solution-forward")
+            .activate(move |win: &Self, _, _| player::step_forward(&win.get_gameboard()))
+            .build();
+        let solution_back_action = gio::ActionEntry::builder("solution-back")
+            .activate(move |win: &Self, _, _| player::step_back(&win.get_gameboard()))
+            .build();
+        let solution_close_action = gio::ActionEntry::builder("solution-close")
+            .activate(move |win: &Self, _, _| player::close(&win.get_gameboard()))
+            .build();
+        let analyze_action = gio::ActionEntry::builder("analyze")
+            .activate(move |win: &Self, _, _| analysis::analyze(win))
+            .build();
+        let export_replay_action = gio::ActionEntry::builder("export-replay")
+            .activate(move |win: &Self, _, _| replay::export(win))
+            .build();
+        let copy_position_action = gio::ActionEntry::builder("copy-position")
+            .activate(move |win: &Self, _, _| win.copy_position())
+            .build();
+        let paste_position_action = gio::ActionEntry::builder("paste-position")
+            .activate(move |win: &Self, _, _| win.paste_position())
+            .build();
+        let open_replay_action = gio::ActionEntry::builder("open-replay")
+            .activate(move |win: &Self, _, _| replay::open(win))
+            .build();
         let undo_action = gio::ActionEntry::builder("undo")
             .activate(move |win: &Self, _, _| win.undo())
             .build();
@@ -220,6 +344,16 @@ impl SolitaireWindow {
             appearance_action,
             drop_action,
             hint_action,
+            play_solution_action,
+            solution_play_action,
+            solution_forward_action,
+            solution_back_action,
+            solution_close_action,
+            analyze_action,
+            export_replay_action,
+            open_replay_action,
+            copy_position_action,
+            paste_position_action,
             undo_action,
             redo_action,
         ]);
@@ -228,98 +362,150 @@ impl SolitaireWindow {
     #[template_callback]
     fn recent_clicked(&self, _row: &adw::ActionRow) {
         let settings = gio::Settings::new(crate::APP_ID);
-        games::load_game(
-            &settings.get::<String>("recent-game"),
-            &self.get_gameboard(),
-        );
+        if let Some(game) = games::get_game(&settings.get::<String>("recent-game")) {
+            self.start_game(game);
+        }
+    }
+
+    fn start_game(&self, game: &'static games::GameDescriptor) {
+        self.start_deal(game, games::Deal::default());
+    }
+
+    /// Deals a new game and opens its page
+    pub fn start_deal(&self, game: &'static games::GameDescriptor, deal: games::Deal) {
+        glib::g_message!("solitaire", "Starting {}", game.id);
+        let settings = gio::Settings::new(crate::APP_ID);
+        settings
+            .set_string("recent-game", game.id)
+            .expect("Failed to save the recent game");
+        self.set_can_drop(false);
+        self.imp().new_game_is_safe.set(false);
         self.imp().nav_view.get().push_by_tag("game");
+        self.imp().game_stack.set_visible_child_name("spinner");
+        let game_board = self.get_gameboard();
+        #[cfg(debug_assertions)]
+        {
+            games::test_solver_state();
+            replay::test_round_trip();
+            games::notation::test_notation();
+            games::test_seeded_deal();
+        }
+
+        self.imp().game_page.set_title(&(game.name)());
+
+        // Run the solver async blocking the UI
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to=window)]
+            self,
+            async move {
+                if let Some(solution) = games::try_game(game.id, &game_board, deal).await {
+                    window.imp().game_stack.set_visible_child_name("grid");
+                    if !solution.is_empty() {
+                        window.set_hint_drop_enabled(true);
+                    }
+                    runtime::set_solution(&game_board, solution);
+                    if let Some(won_fn) = games::is_won_fn(&game_board) {
+                        runtime::set_won_fn(&game_board, won_fn);
+                    }
+                    runtime::notify_ready(&game_board);
+                } else {
+                    if game_board.session().get_should_stop() {
+                        return;
+                    }
+                    // Dealing a seed again gives the same cards, so it can
+                    // only be played without the check
+                    let seeded = deal.seed.is_some();
+                    let (body, accept) = if seeded {
+                        (
+                            gettext("Would you like to play this deal anyway?"),
+                            gettext("Play Anyway"),
+                        )
+                    } else {
+                        (
+                            gettext("Would you like to try again?"),
+                            gettext("Try Again"),
+                        )
+                    };
+                    let retry = games::Deal {
+                        solver_check: deal.solver_check && !seeded,
+                        ..deal
+                    };
+                    let dialog = adw::AlertDialog::builder()
+                        .heading(gettext("Failed to make a winnable game"))
+                        .body(body)
+                        .default_response("accept")
+                        .close_response("delete_event")
+                        .build();
+                    dialog.add_responses(&[
+                        ("accept", accept.as_str()),
+                        ("delete_event", gettext("Go Back").as_str()),
+                    ]);
+                    dialog.set_response_appearance("accept", adw::ResponseAppearance::Suggested);
+                    dialog.connect_response(
+                        Some("accept"),
+                        glib::clone!(
+                            #[weak]
+                            window,
+                            move |_dialog, _response| window.start_deal(game, retry)
+                        ),
+                    );
+
+                    dialog.connect_response(Some("delete_event"), move |dialog, _response| {
+                        dialog
+                            .root()
+                            .unwrap()
+                            .downcast::<SolitaireWindow>()
+                            .unwrap()
+                            .imp()
+                            .nav_view
+                            .pop_to_tag("chooser");
+                    });
+
+                    dialog.present(Some(&window));
+                }
+            }
+        ));
     }
 
     #[template_callback]
     fn populate_game_list(&self, list: &gtk::ListBox) {
-        for game in games::get_games() {
+        for game in games::GAMES {
             let action_row = adw::ActionRow::new();
             let icon = gtk::Image::new();
             icon.set_icon_name(Some("go-next-symbolic"));
             icon.set_valign(gtk::Align::Center);
             action_row.set_activatable(true);
-            action_row.set_property("title", &game);
-            action_row.set_property("subtitle", games::get_game_description(&game));
+            action_row.set_widget_name(game.id);
+            action_row.set_property("title", (game.name)());
+            action_row.set_property("subtitle", (game.description)());
             action_row.add_suffix(&icon);
-            action_row.connect_activated(move |action_row| {
-                let game_name = game.to_owned();
-                glib::g_message!("solitaire", "Starting {game_name}");
-                let window = Self::get_window().unwrap();
-                window.set_can_drop(false);
-                window.imp().new_game_is_safe.set(false);
-                window.imp().nav_view.get().push_by_tag("game");
-                window.imp().game_stack.set_visible_child_name("spinner");
-                let game_board = window.get_gameboard();
-                #[cfg(debug_assertions)]
-                games::test_solver_state();
-
-                window.imp().game_page.set_title(&*game_name);
-
-                // Run the solver async blocking the UI
-                glib::spawn_future_local(glib::clone!(
-                    #[weak]
-                    window,
-                    #[weak]
-                    action_row,
-                    async move {
-                        if let Some(solution) = games::try_game(&*game_name, &game_board).await {
-                            window.imp().game_stack.set_visible_child_name("grid");
-                            if !solution.is_empty() {
-                                window.set_hint_drop_enabled(true);
-                            }
-                            runtime::set_solution(solution);
-                            let won_fn = games::is_won_fn();
-                            runtime::set_won_fn(won_fn);
-                        } else {
-                            if games::solver::get_should_stop() {
-                                return;
-                            }
-                            let dialog = adw::AlertDialog::builder()
-                                .heading(gettext("Failed to make a winnable game"))
-                                .body(gettext("Would you like to try again?"))
-                                .default_response("accept")
-                                .close_response("delete_event")
-                                .build();
-                            dialog.add_responses(&[
-                                ("accept", gettext("Try Again").as_str()),
-                                ("delete_event", gettext("Go Back").as_str()),
-                            ]);
-                            dialog.set_response_appearance(
-                                "accept",
-                                adw::ResponseAppearance::Suggested,
-                            );
-                            let owned_row = action_row.clone();
-                            dialog.connect_response(Some("accept"), move |_dialog, _response| {
-                                owned_row.emit_activate();
-                            });
-
-                            dialog.connect_response(
-                                Some("delete_event"),
-                                move |dialog, _response| {
-                                    dialog
-                                        .root()
-                                        .unwrap()
-                                        .downcast::<SolitaireWindow>()
-                                        .unwrap()
-                                        .imp()
-                                        .nav_view
-                                        .pop_to_tag("chooser");
-                                },
-                            );
-
-                            dialog.present(Some(&window));
-                        }
-                    }
-                ));
-            });
+            action_row.connect_activated(glib::clone!(
+                #[weak(rename_to=this)]
+                self,
+                move |_action_row| this.start_game(game)
+            ));
             list.append(&action_row);
         }
 
+        // Group the games by category, the list is already ordered that way
+        list.set_header_func(|row, before| {
+            let category = games::get_game(&row.widget_name()).map(|game| game.category);
+            let previous = before.and_then(|before| games::get_game(&before.widget_name()));
+            if category.is_none() || previous.map(|game| game.category) == category {
+                row.set_header(None::<&gtk::Widget>);
+                return;
+            }
+            let label = gtk::Label::builder()
+                .label(category.unwrap().name())
+                .halign(gtk::Align::Start)
+                .margin_start(12)
+                .margin_top(12)
+                .margin_bottom(6)
+                .css_classes(["heading"])
+                .build();
+            row.set_header(Some(&label));
+        });
+
         let search_entry = self.imp().search_entry.get();
         list.set_filter_func(glib::clone!(
             #[weak(rename_to=this)]
@@ -358,14 +544,58 @@ impl SolitaireWindow {
         ));
     }
 
+    #[template_callback]
+    fn timeline_changed(&self, timeline: &gtk::Scale) {
+        if self.imp().updating_timeline.get() {
+            return;
+        }
+        let game_board = self.get_gameboard();
+        if runtime::is_autoplaying(&game_board) {
+            // Put it back to where the game is
+            self.update_timeline();
+            return;
+        }
+        player::pause(&game_board);
+        runtime::go_to_move(&game_board, timeline.value().round() as usize);
+    }
+
+    #[template_callback]
+    fn timeline_line_changed(&self, timeline_lines: &gtk::DropDown) {
+        if self.imp().updating_timeline.get() {
+            return;
+        }
+        let game_board = self.get_gameboard();
+        if runtime::is_autoplaying(&game_board) {
+            // Put it back to where the game is
+            self.update_timeline();
+            return;
+        }
+        player::pause(&game_board);
+        runtime::switch_line(&game_board, timeline_lines.selected() as usize);
+    }
+
     #[template_callback]
     fn new_game_clicked(&self, _button: &gtk::Button) {
-        let nav_view = self.imp().nav_view.get();
+        self.leave_game(Self::show_chooser);
+    }
+
+    pub fn show_chooser(&self) {
+        self.imp().nav_view.pop_to_tag("chooser");
+    }
+
+    /// Unloads the game being played, asking first if there is progress to
+    /// lose, and then does `then`
+    pub fn leave_game(&self, then: impl Fn(&Self) + 'static) {
         let game_board = self.get_gameboard();
-        if self.imp().new_game_is_safe.get() {
+        let nothing_to_lose =
+            self.imp().new_game_is_safe.get() || game_board.session().game.borrow().is_none();
+        let unload = move |window: &Self| {
             games::unload(&game_board);
-            games::solver::set_should_stop(true);
-            nav_view.pop_to_tag("chooser");
+            game_board.session().set_should_stop(true);
+            then(window);
+        };
+        if nothing_to_lose {
+            unload(self);
             return;
         }
         let dialog = adw::AlertDialog::builder()
@@ -380,27 +610,26 @@ impl SolitaireWindow {
             ("delete_event", gettext("Keep Current Game").as_str()),
         ]);
 
-        dialog.connect_response(Some("accept"), move |_dialog, _response| {
-            games::unload(&game_board);
-            games::solver::set_should_stop(true);
-            nav_view.pop_to_tag("chooser");
-        });
+        dialog.connect_response(
+            Some("accept"),
+            glib::clone!(
+                #[weak(rename_to=window)]
+                self,
+                move |_dialog, _response| unload(&window)
+            ),
+        );
         dialog.set_response_appearance("accept", adw::ResponseAppearance::Destructive);
         dialog.present(Some(self));
     }
 
-    pub fn get_window() -> Option<SolitaireWindow> {
-        SELF.with(|window| window.borrow().to_owned())
-    }
-
     pub fn incompatible_move_dialog<
         U: Fn(&adw::AlertDialog, &str) + 'static,
-        K: Fn(&adw::AlertDialog, &str) + 'static,
+        K: Fn(&adw::AlertDialog, &str) + Clone + 'static,
     >(
+        &self,
         undo_move: U,
         keep_playing: K,
     ) {
-        let window = Self::get_window().unwrap();
         let dialog = adw::AlertDialog::builder()
             .heading(gettext("Game is no longer winnable"))
             .body(gettext("A recent move has made the game impossible to win"))
@@ -409,16 +638,34 @@ impl SolitaireWindow {
             .build();
         dialog.add_responses(&[
             ("delete_event", &*gettext("Keep Playing")),
+            ("analyze", &*gettext("Analyze")),
             ("undo", &*gettext("Undo Move")),
         ]);
         dialog.set_response_appearance("delete_event", adw::ResponseAppearance::Destructive);
         dialog.connect_response(Some("undo"), undo_move);
-        dialog.connect_response(Some("delete_event"), keep_playing);
-        dialog.present(Some(&window));
+        dialog.connect_response(Some("delete_event"), keep_playing.clone());
+        dialog.connect_response(
+            Some("analyze"),
+            glib::clone!(
+                #[weak(rename_to=window)]
+                self,
+                move |dialog, response| {
+                    keep_playing(dialog, response);
+                    analysis::analyze(&window);
+                }
+            ),
+        );
+        dialog.present(Some(self));
     }
 
     pub fn won_dialog(&self) {
         self.imp().new_game_is_safe.set(true);
+        player::close(&self.get_gameboard());
+        automation::emit_won();
+        self.get_gameboard().announce(
+            &gettext("You have won"),
+            gtk::AccessibleAnnouncementPriority::High,
+        );
         let dialog = adw::AlertDialog::builder()
             .heading(gettext("You have won"))
             .body(gettext("Congratulations, you have solved the game"))
@@ -431,12 +678,181 @@ impl SolitaireWindow {
         dialog.set_response_appearance("new_game", adw::ResponseAppearance::Suggested);
         let nav_view = self.imp().nav_view.get();
         let game_board = self.get_gameboard();
-        dialog.connect_response(Some("new_game"), move |_dialog, _response| {
+        dialog.connect_response(
+            Some("new_game"),
+            glib::clone!(
+                #[strong]
+                game_board,
+                move |_dialog, _response| {
+                    games::unload(&game_board);
+                    game_board.session().set_should_stop(true);
+                    nav_view.pop_to_tag("chooser");
+                }
+            ),
+        );
+        celebration::start(
+            &game_board,
+            glib::clone!(
+                #[weak(rename_to=window)]
+                self,
+                move || dialog.present(Some(&window))
+            ),
+        );
+    }
+
+    /// Shows how far loading a card theme got, or hides the progress with `None`
+    pub fn set_theme_progress(&self, fraction: Option<f64>) {
+        let theme_progress = self.imp().theme_progress.get();
+        theme_progress.set_visible(fraction.is_some());
+        theme_progress.set_fraction(fraction.unwrap_or(0.0));
+    }
+
+    /// Shows or hides the controls of the solution player. Replays are only
+    /// watched, so they can't be taken over.
+    pub fn set_solution_controls(&self, shown: bool, can_take_over: bool) {
+        self.imp().solution_bar.set_revealed(shown);
+        self.imp().solution_take_over.set_visible(can_take_over);
+    }
+
+    /// Deals the game and sets up the board in the position read from the file
+    pub fn open_position(&self, game: &'static games::GameDescriptor, file: gio::File) {
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to=window)]
+            self,
+            async move {
+                let layout = match file.load_contents_future().await {
+                    Ok((contents, _)) => std::str::from_utf8(&contents)
+                        .map_err(|_| gettext("The text is not a position"))
+                        .and_then(games::notation::parse_position),
+                    Err(error) => {
+                        glib::g_warning!("solitaire", "Failed to read position: {error}");
+                        Err(gettext("Couldn't read the position"))
+                    }
+                };
+                match layout {
+                    Ok(layout) => window.leave_game(move |window| {
+                        window.show_chooser();
+                        window.start_position(game, &layout);
+                    }),
+                    Err(message) => window.show_toast(&message),
+                }
+            }
+        ));
+    }
+
+    fn start_position(&self, game: &'static games::GameDescriptor, layout: &[(String, Vec<u8>)]) {
+        self.set_can_drop(false);
+        self.imp().new_game_is_safe.set(false);
+        self.imp().game_page.set_title(&(game.name)());
+        self.imp().nav_view.get().push_by_tag("game");
+        self.imp().game_stack.set_visible_child_name("grid");
+        let game_board = self.get_gameboard();
+        game_board.session().set_should_stop(false);
+        games::load_game(game.id, &game_board, None);
+        if let Some(won_fn) = games::is_won_fn(&game_board) {
+            runtime::set_won_fn(&game_board, won_fn);
+        }
+        if !runtime::set_position(&game_board, layout) {
             games::unload(&game_board);
-            games::solver::set_should_stop(true);
-            nav_view.pop_to_tag("chooser");
-        });
-        dialog.present(Some(self));
+            self.show_chooser();
+            self.show_toast(&gettext("The position doesn't fit this game"));
+        }
+    }
+
+    /// Shows the game page for a replay of the game
+    pub fn show_replay(&self, game: &'static games::GameDescriptor) {
+        self.set_can_drop(false);
+        self.imp().new_game_is_safe.set(true);
+        // Translators: the title of the game page while a replay plays, like “Klondike Replay”
+        let title = gettext("{game} Replay").replace("{game}", &(game.name)());
+        self.imp().game_page.set_title(&title);
+        self.imp().nav_view.get().push_by_tag("game");
+        self.imp().game_stack.set_visible_child_name("grid");
+    }
+
+    pub fn set_solution_playing(&self, playing: bool) {
+        let solution_play = self.imp().solution_play.get();
+        if playing {
+            solution_play.set_icon_name("media-playback-pause-symbolic");
+            solution_play.set_tooltip_text(Some(&*gettext("Pause")));
+        } else {
+            solution_play.set_icon_name("media-playback-start-symbolic");
+            solution_play.set_tooltip_text(Some(&*gettext("Play")));
+        }
+    }
+
+    pub fn show_toast(&self, title: &str) {
+        self.imp().toast_overlay.add_toast(adw::Toast::new(title));
+    }
+
+    /// Moves the timeline to the current move and lists the lines of play,
+    /// with a mark where each one leaves the current line
+    pub fn update_timeline(&self) {
+        let imp = self.imp();
+        let game_board = self.get_gameboard();
+        let (made, total) = runtime::get_timeline(&game_board);
+        let (lines, current) = runtime::get_lines(&game_board);
+        // Moving through the history while autoplay makes moves would undo them
+        let autoplaying = runtime::is_autoplaying(&game_board);
+        imp.updating_timeline.set(true);
+
+        let timeline = imp.timeline.get();
+        timeline.clear_marks();
+        timeline.adjustment().set_upper(total as f64);
+        timeline.set_value(made as f64);
+        timeline.set_sensitive(total > 0 && !autoplaying);
+        let mut forks = lines
+            .iter()
+            .enumerate()
+            .filter(|(i, (_, fork))| *i != current && *fork < total)
+            .map(|(_, (_, fork))| *fork)
+            .collect::<Vec<usize>>();
+        forks.sort_unstable();
+        forks.dedup();
+        for fork in forks {
+            timeline.add_mark(fork as f64, gtk::PositionType::Bottom, None);
+        }
+
+        let names = lines
+            .iter()
+            .enumerate()
+            .map(|(i, (n_moves, _))| {
+                // Translators: {line} is the number of the line of play, {moves} how many moves it has
+                gettext("Line {line}, {moves}")
+                    .replace("{line}", &(i + 1).to_string())
+                    .replace(
+                        "{moves}",
+                        &ngettext("{} move", "{} moves", *n_moves as u32)
+                            .replace("{}", &n_moves.to_string()),
+                    )
+            })
+            .collect::<Vec<String>>();
+        let timeline_lines = imp.timeline_lines.get();
+        timeline_lines.set_model(Some(&gtk::StringList::new(
+            &names.iter().map(String::as_str).collect::<Vec<&str>>(),
+        )));
+        timeline_lines.set_selected(current as u32);
+        timeline_lines.set_visible(lines.len() > 1);
+        timeline_lines.set_sensitive(!autoplaying);
+        imp.updating_timeline.set(false);
+    }
+
+    /// Shows the score in the header bar, for the games that keep one
+    pub fn update_score(&self) {
+        let game_board = self.get_gameboard();
+        // Games call this while they make moves, so it can't look at the game itself
+        let scored =
+            games::get_game(game_board.session().game_id.get()).is_some_and(|game| game.scored);
+        let score = self.imp().score.get();
+        score.set_visible(scored);
+        // Translators: {score} is the number of points, like “Score 35”
+        let label = gettext("Score {score}")
+            .replace("{score}", &runtime::get_score(&game_board).to_string());
+        score.set_label(&label);
+    }
+
+    pub fn set_no_moves(&self, no_moves: bool) {
+        self.imp().no_moves_banner.set_revealed(no_moves);
     }
 
     pub fn set_can_drop(&self, can_drop: bool) {
@@ -463,12 +879,14 @@ impl SolitaireWindow {
         }
     }
 
+    /// Drop plays the solution, so it needs one. Hints fall back to the moves
+    /// that can be made, so they stay available without a solution.
     pub fn set_hint_drop_enabled(&self, enabled: bool) {
         self.lookup_action("hint")
             .unwrap()
             .downcast::<gio::SimpleAction>()
             .unwrap()
-            .set_enabled((!self.imp().can_drop.get()) && enabled);
+            .set_enabled(!self.imp().can_drop.get());
         self.lookup_action("drop")
             .unwrap()
             .downcast::<gio::SimpleAction>()